@@ -0,0 +1,190 @@
+// Baselines for the encode and decode paths. Run with `cargo bench`; the url-safe
+// groups need the default features.
+//
+// To check a change for regressions, save a baseline on the commit before it
+// and compare against it on the same machine:
+//
+//   cargo bench --bench codec -- --save-baseline before
+//   cargo bench --bench codec -- --baseline before
+//
+// criterion reports each group's time and throughput against `before`, and
+// flags changes outside its noise threshold.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::BTreeMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use psi_spell_encode_wasm::{bytes_to_url_safe, url_safe_to_bytes, Mod, Piece, Spell, SpellData};
+
+// Counts every `realloc` so the `bin_reallocations` group can show what
+// reserving up front saves.
+struct CountingAlloc;
+
+static REALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        REALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn reallocs<T>(f: impl FnOnce() -> T) -> usize {
+    let before = REALLOCS.load(Ordering::Relaxed);
+    black_box(f());
+    REALLOCS.load(Ordering::Relaxed) - before
+}
+
+// A row-major run of pieces in the shape real spells take: mostly operators
+// wired to their neighbours, with a constant every few pieces and the odd
+// comment or modded key.
+fn spell(pieces: usize) -> Spell {
+    let pieces = (0..pieces)
+        .map(|i| {
+            let (x, y) = ((i % 9) as u8, (i / 9) as u8);
+            let data = if i % 4 == 0 {
+                SpellData {
+                    key: "psi:constant_number".to_owned(),
+                    params: None,
+                    constant: Some(format!("{}", i * 3)),
+                    comment: None,
+                    #[cfg(feature = "json")]
+                    extra: None,
+                }
+            } else {
+                SpellData {
+                    key: if i % 7 == 0 {
+                        "magical_psi:trick_conjure_star".to_owned()
+                    } else {
+                        "psi:operator_vector_construct".to_owned()
+                    },
+                    params: Some(BTreeMap::from([
+                        ("_x".to_owned(), 3),
+                        ("_y".to_owned(), 1),
+                        ("_z".to_owned(), 0),
+                    ])),
+                    constant: None,
+                    comment: (i % 5 == 0).then(|| format!("step {i}")),
+                    #[cfg(feature = "json")]
+                    extra: None,
+                }
+            };
+            Piece { data, x, y }
+        })
+        .collect();
+
+    Spell {
+        name: "bench".to_owned(),
+        mods: vec![Mod {
+            name: "magical_psi".to_owned(),
+            version: "1.0".to_owned(),
+        }],
+        pieces,
+    }
+}
+
+const SIZES: [usize; 3] = [4, 27, 81];
+
+fn bin(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bin");
+    for size in SIZES {
+        let spell = spell(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &spell, |b, spell| {
+            b.iter(|| black_box(spell).bin().unwrap())
+        });
+    }
+    group.finish();
+}
+
+// `reserved` is `bin` as it is; `grown` pushes the same bytes into an empty
+// `Vec`, which doubles its way up just as `bin`'s output did before it
+// reserved. The reallocations each makes are printed once per size.
+fn bin_reallocations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bin_reallocations");
+    for size in SIZES {
+        let spell = spell(size);
+        let bin = spell.bin().unwrap();
+        let grown = |bin: &[u8]| {
+            let mut out = Vec::new();
+            for &byte in bin {
+                out.push(byte);
+            }
+            out
+        };
+        println!(
+            "bin_reallocations/{size}: reserved {}, grown {}",
+            reallocs(|| spell.bin().unwrap()),
+            reallocs(|| grown(&bin)),
+        );
+
+        group.bench_with_input(BenchmarkId::new("reserved", size), &spell, |b, spell| {
+            b.iter(|| black_box(spell).bin().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("grown", size), &bin, |b, bin| {
+            b.iter(|| grown(black_box(bin)))
+        });
+    }
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for size in SIZES {
+        let bin = spell(size).bin().unwrap();
+        group.throughput(Throughput::Bytes(bin.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &bin, |b, bin| {
+            b.iter(|| Spell::decode(black_box(bin)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn to_url_safe(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bytes_to_url_safe");
+    for size in SIZES {
+        let bin = spell(size).bin().unwrap();
+        group.throughput(Throughput::Bytes(bin.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &bin, |b, bin| {
+            b.iter(|| bytes_to_url_safe(black_box(bin)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn from_url_safe(c: &mut Criterion) {
+    let mut group = c.benchmark_group("url_safe_to_bytes");
+    for size in SIZES {
+        let url_safe = bytes_to_url_safe(&spell(size).bin().unwrap()).unwrap();
+        group.throughput(Throughput::Bytes(url_safe.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(size),
+            &url_safe,
+            |b, url_safe| b.iter(|| url_safe_to_bytes(black_box(url_safe)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bin,
+    bin_reallocations,
+    decode,
+    to_url_safe,
+    from_url_safe
+);
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use psi_spell_encode_wasm::Spell;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Spell::decode(data);
+});
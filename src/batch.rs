@@ -0,0 +1,108 @@
+use alloc::vec::Vec;
+
+use crate::{DecodeError, EncodeError, Spell};
+
+/// Spells back to back, each behind its length as a little-endian u32. The
+/// binary form has no end marker of its own, so the length is what lets a
+/// reader find the next spell, and skip past one that fails to decode.
+pub fn encode_many(spells: &[Spell]) -> Result<Vec<u8>, EncodeError> {
+    let mut out = Vec::new();
+    for spell in spells {
+        let bin = spell.bin()?;
+        out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        out.extend_from_slice(&bin);
+    }
+    Ok(out)
+}
+
+// Splits off the next frame, or fails if its length runs past the data.
+fn next_frame<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let Some((len, rest)) = data.split_first_chunk::<4>() else {
+        return Err(DecodeError::UnexpectedEof);
+    };
+    let len = u32::from_le_bytes(*len) as usize;
+    if rest.len() < len {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    let (frame, rest) = rest.split_at(len);
+    *data = rest;
+    Ok(frame)
+}
+
+pub fn decode_many(mut data: &[u8]) -> Result<Vec<Spell>, DecodeError> {
+    let mut spells = Vec::new();
+    while !data.is_empty() {
+        spells.push(Spell::decode(next_frame(&mut data)?)?);
+    }
+    Ok(spells)
+}
+
+/// Keeps going past spells that fail to decode, collecting their errors in
+/// order. A frame length that runs past the data ends the batch, since no later
+/// boundary can be trusted after it.
+pub fn decode_many_lenient(mut data: &[u8]) -> (Vec<Spell>, Vec<DecodeError>) {
+    let mut spells = Vec::new();
+    let mut errors = Vec::new();
+    while !data.is_empty() {
+        let frame = match next_frame(&mut data) {
+            Ok(frame) => frame,
+            Err(err) => {
+                errors.push(err);
+                break;
+            }
+        };
+        match Spell::decode(frame) {
+            Ok(spell) => spells.push(spell),
+            Err(err) => errors.push(err),
+        }
+    }
+    (spells, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn spells() -> Vec<Spell> {
+        vec![
+            spell("first", vec![piece("psi:selector_caster", 0, 0)]),
+            spell("second", vec![]),
+        ]
+    }
+
+    #[test]
+    fn frames_are_length_prefixed() {
+        let data = encode_many(&spells()).unwrap();
+        let first = spells()[0].bin().unwrap();
+        assert_eq!(data[..4], (first.len() as u32).to_le_bytes());
+        assert_eq!(
+            data[4..],
+            [&first, &8u32.to_le_bytes()[..], b"second\0]"].concat()
+        );
+        assert_eq!(decode_many(&data).unwrap(), spells());
+        assert_eq!(decode_many(&[]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn lenient_decode_skips_bad_frames() {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend_from_slice(b"\xff\xff");
+        data.extend_from_slice(&encode_many(&spells()).unwrap());
+
+        assert!(decode_many(&data).is_err());
+        let (decoded, errors) = decode_many_lenient(&data);
+        assert_eq!(decoded, spells());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn frame_running_past_the_data_ends_the_batch() {
+        let mut data = encode_many(&spells()).unwrap();
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.extend_from_slice(b"s\0]");
+        let (decoded, errors) = decode_many_lenient(&data);
+        assert_eq!(decoded, spells());
+        assert_eq!(errors, vec![DecodeError::UnexpectedEof]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use psi_spell_encode_wasm::{Error, Spell};
+
+const USAGE: &str = "\
+usage: psi-encode [--snbt] [FILE]   SNBT from FILE or stdin to a url-safe string
+       psi-encode --decode [STRING] url-safe STRING or stdin to SNBT";
+
+fn read_input(arg: Option<&str>, literal: bool) -> io::Result<String> {
+    match arg {
+        Some("-") | None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            Ok(input)
+        }
+        Some(arg) if literal => Ok(arg.to_string()),
+        Some(path) => std::fs::read_to_string(path),
+    }
+}
+
+fn encode(snbt: &str) -> Result<String, Error> {
+    Spell::from_snbt(snbt.trim())?.to_url_safe()
+}
+
+fn decode(url_safe: &str) -> Result<String, Error> {
+    url_safe.trim().parse::<Spell>()?.to_snbt()
+}
+
+fn run(args: &[String]) -> Result<String, Box<dyn std::error::Error>> {
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["--decode"] => Ok(decode(&read_input(None, true)?)?),
+        ["--decode", input] => Ok(decode(&read_input(Some(input), true)?)?),
+        [] | ["--snbt"] => Ok(encode(&read_input(None, false)?)?),
+        ["--snbt", path] => Ok(encode(&read_input(Some(path), false)?)?),
+        [path] if !path.starts_with("--") => Ok(encode(&read_input(Some(path), false)?)?),
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(out) => {
+            println!("{out}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with(args: &[&str]) -> Result<String, String> {
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        run(&args).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn unknown_flags_print_usage() {
+        for args in [
+            &["--encode"][..],
+            &["--decode", "a", "b"],
+            &["--snbt", "a", "b"],
+            &["--", "file"],
+        ] {
+            assert_eq!(run_with(args), Err(USAGE.to_string()), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn missing_file_is_reported() {
+        let path = "/nonexistent/spell.snbt";
+        let expected = std::fs::read_to_string(path).unwrap_err().to_string();
+        assert_eq!(run_with(&[path]), Err(expected));
+    }
+}
@@ -0,0 +1,489 @@
+use alloc::{
+    borrow::{Cow, ToOwned},
+    collections::BTreeMap,
+    string::String,
+    vec::Vec,
+};
+
+use serde::Serialize;
+
+use crate::{
+    format::{self, Layout, BUILTIN_PARAMS, FLAG_EXTENDED_POSITIONS, FLAG_PARAM_TABLE, HEADER_TAG},
+    reader::Reader,
+    DecodeError, DecodeOptions, Field, Mod, Piece, Spell, SpellData,
+};
+
+/// Views into the binary form, for decoding in bulk without an allocation per
+/// string. Only keys, which get their `psi:` prefix back, and params decoded
+/// leniently ever need one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpellRef<'a> {
+    pub name: &'a str,
+    pub mods: Vec<ModRef<'a>>,
+    pub pieces: Vec<PieceRef<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModRef<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceRef<'a> {
+    pub data: SpellDataRef<'a>,
+    pub x: u8,
+    pub y: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpellDataRef<'a> {
+    pub key: Cow<'a, str>,
+    pub params: Option<BTreeMap<Cow<'a, str>, u8>>,
+    pub constant: Option<&'a str>,
+    pub comment: Option<&'a str>,
+}
+
+impl SpellRef<'_> {
+    pub fn into_owned(self) -> Spell {
+        Spell {
+            name: self.name.to_owned(),
+            mods: self
+                .mods
+                .into_iter()
+                .map(|m| Mod {
+                    name: m.name.to_owned(),
+                    version: m.version.to_owned(),
+                })
+                .collect(),
+            pieces: self
+                .pieces
+                .into_iter()
+                .map(|p| Piece {
+                    data: SpellData {
+                        key: p.data.key.into_owned(),
+                        params: p.data.params.map(|params| {
+                            params
+                                .into_iter()
+                                .map(|(name, side)| (name.into_owned(), side))
+                                .collect()
+                        }),
+                        constant: p.data.constant.map(str::to_owned),
+                        comment: p.data.comment.map(str::to_owned),
+                        #[cfg(feature = "json")]
+                        extra: None,
+                    },
+                    x: p.x,
+                    y: p.y,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<SpellRef<'_>> for Spell {
+    fn from(value: SpellRef<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+/// Which parts of the format a decoded spell used, for corpus analysis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    pub bytes: usize,
+    pub pieces: usize,
+    pub constants: usize,
+    pub builtin_params: usize,
+    /// Custom params written out in full.
+    pub custom_params: usize,
+    /// Custom params referring to the param table.
+    pub interned_params: usize,
+    pub param_table_len: usize,
+    pub extended_positions: bool,
+}
+
+/// How a param name was written in the binary form. Decoding drops this, since
+/// Psi only ever sees the name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ParamOrigin {
+    /// An index into the builtin param names.
+    Builtin { index: u8 },
+    /// Spelled out in full behind CUSTOM_PARAM.
+    Custom,
+    /// A reference into the spell's param table.
+    Interned { index: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PieceParamOrigins {
+    pub x: u8,
+    pub y: u8,
+    pub params: BTreeMap<String, ParamOrigin>,
+}
+
+impl Spell {
+    /// One entry per piece with params, in piece order.
+    pub fn param_origins(data: &[u8]) -> Result<Vec<PieceParamOrigins>, DecodeError> {
+        let mut origins: BTreeMap<usize, BTreeMap<String, ParamOrigin>> = BTreeMap::new();
+        let (spell, _, consumed) = Spell::decode_prefix_with(
+            data,
+            &BUILTIN_PARAMS,
+            DecodeOptions::default(),
+            &mut |piece, name, origin| {
+                origins
+                    .entry(piece)
+                    .or_default()
+                    .insert(name.to_owned(), origin);
+            },
+        )?;
+        if consumed < data.len() {
+            return Err(DecodeError::TrailingData { offset: consumed });
+        }
+
+        Ok(spell
+            .pieces
+            .iter()
+            .enumerate()
+            .filter(|(_, piece)| piece.data.params.is_some())
+            .map(|(i, piece)| PieceParamOrigins {
+                x: piece.x,
+                y: piece.y,
+                params: origins.remove(&i).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    pub fn decode_with_stats(data: &[u8]) -> Result<(Self, DecodeStats), DecodeError> {
+        let mut origins = Vec::new();
+        let (spell, layout, consumed) = Spell::decode_prefix_with(
+            data,
+            &BUILTIN_PARAMS,
+            DecodeOptions::default(),
+            &mut |_, _, origin| origins.push(origin),
+        )?;
+        if consumed < data.len() {
+            return Err(DecodeError::TrailingData { offset: consumed });
+        }
+
+        let mut stats = DecodeStats {
+            bytes: consumed,
+            pieces: spell.pieces.len(),
+            param_table_len: layout.param_table.as_ref().map_or(0, Vec::len),
+            extended_positions: layout.extended,
+            ..Default::default()
+        };
+        stats.constants = spell
+            .pieces
+            .iter()
+            .filter(|piece| piece.data.constant.is_some())
+            .count();
+        for origin in origins {
+            match origin {
+                ParamOrigin::Builtin { .. } => stats.builtin_params += 1,
+                ParamOrigin::Custom => stats.custom_params += 1,
+                ParamOrigin::Interned { .. } => stats.interned_params += 1,
+            }
+        }
+
+        Ok((spell.into_owned(), stats))
+    }
+
+    pub fn decode_borrowed(data: &[u8]) -> Result<SpellRef<'_>, DecodeError> {
+        Spell::decode_borrowed_with_options(data, DecodeOptions::default())
+    }
+
+    pub fn decode_borrowed_with_options(
+        data: &[u8],
+        options: DecodeOptions,
+    ) -> Result<SpellRef<'_>, DecodeError> {
+        let (spell, _, consumed) = Spell::decode_prefix(data, options)?;
+        if consumed < data.len() {
+            return Err(DecodeError::TrailingData { offset: consumed });
+        }
+        Ok(spell)
+    }
+
+    /// Reads the name and nothing after it, for listings that show names only.
+    pub fn peek_name(data: &[u8]) -> Result<String, DecodeError> {
+        let mut reader = Reader::new(data);
+        read_flags(&mut reader, data)?;
+        Ok(reader.read_str_until_nul(Field::Name)?.to_owned())
+    }
+
+    /// For spells from a Psi whose builtin params differ from BUILTIN_PARAMS:
+    /// builtin index `i` decodes to `params[i]`. Custom and interned params are
+    /// unaffected.
+    pub fn decode_with_params(data: &[u8], params: &[&str]) -> Result<Spell, DecodeError> {
+        let (spell, _, consumed) =
+            Spell::decode_prefix_with(data, params, DecodeOptions::default(), &mut |_, _, _| {})?;
+        if consumed < data.len() {
+            return Err(DecodeError::TrailingData { offset: consumed });
+        }
+        Ok(spell.into_owned())
+    }
+
+    pub(crate) fn decode_prefix(
+        data: &[u8],
+        options: DecodeOptions,
+    ) -> Result<(SpellRef<'_>, Layout<'_>, usize), DecodeError> {
+        Spell::decode_prefix_with(data, &BUILTIN_PARAMS, options, &mut |_, _, _| {})
+    }
+
+    // `on_param` also gets the index of the piece each param belongs to.
+    fn decode_prefix_with<'a>(
+        data: &'a [u8],
+        builtins: &'a [&'a str],
+        options: DecodeOptions,
+        on_param: &mut dyn FnMut(usize, &str, ParamOrigin),
+    ) -> Result<(SpellRef<'a>, Layout<'a>, usize), DecodeError> {
+        let Preamble {
+            mut reader,
+            name,
+            mods,
+            layout,
+        } = read_preamble(data, builtins)?;
+        let mut pieces = Vec::new();
+
+        let mut consumed = reader.pos();
+        while !reader.is_empty() {
+            if pieces.len() == options.max_pieces {
+                return Err(DecodeError::TooManyPieces {
+                    max: options.max_pieces,
+                });
+            }
+            let index = pieces.len();
+            let mut on_param = |name: &str, origin| on_param(index, name, origin);
+            match format::decode_piece(&mut reader, &layout, options, &mut on_param) {
+                Ok(piece) => pieces.push(piece),
+                Err(DecodeError::UnexpectedEof) => break,
+                Err(e) => return Err(e),
+            }
+            consumed = reader.pos();
+        }
+
+        Ok((SpellRef { name, mods, pieces }, layout, consumed))
+    }
+}
+
+// Everything before the first piece, with the reader left on it.
+pub(crate) struct Preamble<'a> {
+    pub(crate) reader: Reader<'a>,
+    pub(crate) name: &'a str,
+    pub(crate) mods: Vec<ModRef<'a>>,
+    pub(crate) layout: Layout<'a>,
+}
+
+// Zero without a header. A bit this version does not know could change how
+// anything after it is laid out, so it fails the decode rather than being
+// skipped.
+fn read_flags(reader: &mut Reader, data: &[u8]) -> Result<u8, DecodeError> {
+    if data.first() != Some(&HEADER_TAG) {
+        return Ok(0);
+    }
+    reader.next()?;
+    let flags = reader.next()?;
+    let unknown = flags & !(FLAG_EXTENDED_POSITIONS | FLAG_PARAM_TABLE);
+    if unknown != 0 {
+        return Err(DecodeError::UnknownFlags(unknown));
+    }
+    Ok(flags)
+}
+
+pub(crate) fn read_preamble<'a>(
+    data: &'a [u8],
+    builtins: &'a [&'a str],
+) -> Result<Preamble<'a>, DecodeError> {
+    let mut reader = Reader::new(data);
+    let flags = read_flags(&mut reader, data)?;
+    let mut layout = Layout {
+        extended: flags & FLAG_EXTENDED_POSITIONS != 0,
+        param_table: None,
+        builtins,
+    };
+    let name = reader.read_str_until_nul(Field::Name)?;
+    let mut mods = Vec::new();
+
+    {
+        // Usually a string cut off part way through the mods list.
+        let m = reader
+            .read_until(b']')
+            .map_err(|_| DecodeError::MissingModsTerminator)?;
+        for m in m.split(|b| *b == b';').filter(|m| !m.is_empty()) {
+            let mut parts = m.splitn(2, |b| *b == b',');
+            let name = parts.next().unwrap_or_default();
+            let version = parts.next().unwrap_or_default();
+            mods.push(ModRef {
+                name: reader.utf8(name, Field::ModName)?,
+                version: reader.utf8(version, Field::ModVersion)?,
+            })
+        }
+    }
+
+    if flags & FLAG_PARAM_TABLE != 0 {
+        let count = reader.next()?;
+        let table = (0..count)
+            .map(|_| reader.read_str_until_nul(Field::ParamName))
+            .collect::<Result<_, DecodeError>>()?;
+        layout.param_table = Some(table);
+    }
+
+    Ok(Preamble {
+        reader,
+        name,
+        mods,
+        layout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::BTreeMap};
+
+    use super::ParamOrigin;
+    use crate::{testing::*, DecodeError, DecodeOptions, Spell};
+
+    // A custom param spelled like the builtin `_target`, which the encoder
+    // never writes but older or foreign encoders might.
+    const CUSTOM_TARGET: &[u8] = b"s\0]\x00connector\0\0\x02\xff_target\0\x01\x01\x02";
+
+    #[test]
+    fn extended_position_round_trips() {
+        let spell = spell("far", vec![piece("psi:selector_caster", 20, 30)]);
+        let bin = round_trip(&spell);
+        assert_eq!(&bin[..2], &[1, 1]);
+    }
+
+    #[test]
+    fn unknown_flag_bits_are_rejected() {
+        let mut bin = spell("far", vec![piece("psi:selector_caster", 20, 30)])
+            .bin()
+            .unwrap();
+        bin[1] |= 0b1000_0100;
+        assert_eq!(
+            Spell::decode(&bin),
+            Err(DecodeError::UnknownFlags(0b1000_0100))
+        );
+        assert_eq!(
+            Spell::peek_name(&bin),
+            Err(DecodeError::UnknownFlags(0b1000_0100))
+        );
+    }
+
+    #[test]
+    fn param_origins_follow_the_bytes_not_the_name() {
+        let origins = Spell::param_origins(CUSTOM_TARGET).unwrap();
+        assert_eq!(origins.len(), 1);
+        assert_eq!(
+            origins[0].params,
+            BTreeMap::from([
+                ("_target".to_string(), ParamOrigin::Custom),
+                ("_number".to_string(), ParamOrigin::Builtin { index: 1 }),
+            ])
+        );
+    }
+
+    #[test]
+    fn param_origins_report_interned_params() {
+        let pieces = (0..3)
+            .map(|x| with_params(piece("psi:connector", x, 0), &[("_my_long_param", 1)]))
+            .collect();
+        let bin = spell("table", pieces).bin().unwrap();
+        let origins = Spell::param_origins(&bin).unwrap();
+        assert_eq!(origins.len(), 3);
+        for piece in origins {
+            assert_eq!(
+                piece.params["_my_long_param"],
+                ParamOrigin::Interned { index: 0 }
+            );
+        }
+    }
+
+    #[test]
+    fn decode_stats_count_params_by_how_they_were_written() {
+        let (_, stats) = Spell::decode_with_stats(CUSTOM_TARGET).unwrap();
+        assert_eq!(stats.builtin_params, 1);
+        assert_eq!(stats.custom_params, 1);
+        assert_eq!(stats.interned_params, 0);
+    }
+
+    #[test]
+    fn borrowed_decode_points_into_the_input() {
+        let data: &[u8] = b"s\0]\x00phi:trick_far\0note\0\xfe\x10connector\0\0\xff7\0";
+        let spell = Spell::decode_borrowed(data).unwrap();
+        let within = |s: &str| data.as_ptr_range().contains(&s.as_ptr());
+
+        assert!(within(spell.name));
+        let [namespaced, bare] = &spell.pieces[..] else {
+            panic!("expected two pieces, got {:?}", spell.pieces);
+        };
+        assert!(matches!(namespaced.data.key, Cow::Borrowed(key) if within(key)));
+        assert!(within(namespaced.data.comment.unwrap()));
+        // The `psi:` prefix was never stored, so only this key is allocated.
+        assert!(matches!(&bare.data.key, Cow::Owned(key) if key == "psi:connector"));
+        assert!(within(bare.data.constant.unwrap()));
+
+        assert_eq!(spell.into_owned(), Spell::decode(data).unwrap());
+    }
+
+    #[test]
+    fn trailing_bytes_are_reported_or_left_to_the_caller() {
+        let spell = spell("s", vec![piece("psi:connector", 0, 0)]);
+        let bin = spell.bin().unwrap();
+        let blob = [bin.as_slice(), b"\x01extra"].concat();
+        assert_eq!(
+            Spell::decode(&blob),
+            Err(DecodeError::TrailingData { offset: bin.len() })
+        );
+        assert_eq!(Spell::decode_with_remainder(&blob), Ok((spell, bin.len())));
+    }
+
+    #[test]
+    fn piece_count_is_capped() {
+        let pieces = (0..3).map(|x| piece("psi:connector", x, 0)).collect();
+        let bin = spell("many", pieces).bin().unwrap();
+        let options = |max_pieces| DecodeOptions {
+            max_pieces,
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            Spell::decode_with_options(&bin, options(2)),
+            Err(DecodeError::TooManyPieces { max: 2 })
+        );
+        assert!(Spell::decode_with_options(&bin, options(3)).is_ok());
+    }
+
+    #[test]
+    fn mods_list_needs_its_terminator() {
+        assert_eq!(
+            Spell::decode(b"s\0psi,r1.1;phi,2"),
+            Err(DecodeError::MissingModsTerminator)
+        );
+        let spell = Spell::decode(b"s\0psi,r1.1;phi]").unwrap();
+        let mods: Vec<_> = spell.mods.iter().map(|m| (m.name(), m.version())).collect();
+        assert_eq!(mods, [("psi", "r1.1"), ("phi", "")]);
+    }
+
+    #[test]
+    fn peek_name_stops_after_the_name() {
+        assert_eq!(Spell::peek_name(b"blink\0\xff\xff").unwrap(), "blink");
+        let far = spell("far", vec![piece("psi:connector", 20, 0)]);
+        assert_eq!(Spell::peek_name(&far.bin().unwrap()).unwrap(), "far");
+        assert_eq!(Spell::peek_name(b"blink"), Err(DecodeError::UnexpectedEof));
+    }
+
+    #[test]
+    fn custom_builtin_table_renames_only_indexed_params() {
+        let table = ["_first", "_second"];
+        let spell = Spell::decode_with_params(CUSTOM_TARGET, &table).unwrap();
+        let params = spell.pieces[0].data.params.as_ref().unwrap();
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            [(&"_second".to_string(), &2), (&"_target".to_string(), &1)]
+        );
+        assert_eq!(
+            Spell::decode_with_params(CUSTOM_TARGET, &table[..1]),
+            Err(DecodeError::UnknownParam(1))
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use std::sync::OnceLock;
+
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+use crate::{DecodeError, Error, Spell};
+
+const ZSTD_DICT: &[u8] = include_bytes!("./zstd_dict");
+const LEVEL: i32 = 22;
+
+// Digesting the dictionary costs far more than compressing a spell with it, so
+// the default one is prepared once and shared by every thread; zstd never
+// writes to a prepared dictionary after building it.
+fn encoder_dict() -> &'static EncoderDictionary<'static> {
+    static DICT: OnceLock<EncoderDictionary<'static>> = OnceLock::new();
+    DICT.get_or_init(|| EncoderDictionary::copy(ZSTD_DICT, LEVEL))
+}
+
+fn decoder_dict() -> &'static DecoderDictionary<'static> {
+    static DICT: OnceLock<DecoderDictionary<'static>> = OnceLock::new();
+    DICT.get_or_init(|| DecoderDictionary::copy(ZSTD_DICT))
+}
+
+// zstd frames always open with the 0xFD2FB528 magic number, so a leading
+// RAW_TAG can only mean the payload was stored uncompressed.
+pub(crate) const RAW_TAG: u8 = 0;
+// Followed by a big-endian CRC-16 of the rest, which is itself a zstd frame or
+// a RAW_TAG payload. Only written by `compress_checked`.
+const CHECKSUM_TAG: u8 = 1;
+
+// CRC-16/CCITT-FALSE. Two bytes is plenty to catch a truncated or mistyped string.
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for b in bytes {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+pub fn compress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let compressed =
+        zstd::bulk::Compressor::with_prepared_dictionary(encoder_dict())?.compress(bytes)?;
+    Ok(or_raw(bytes, compressed))
+}
+
+pub fn compress_checked(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let payload = compress(bytes)?;
+    let mut checked = Vec::with_capacity(payload.len() + 3);
+    checked.push(CHECKSUM_TAG);
+    checked.extend_from_slice(&crc16(&payload).to_be_bytes());
+    checked.extend_from_slice(&payload);
+    Ok(checked)
+}
+
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    unwrap_payload(bytes, &|frame| {
+        zstd::stream::Decoder::with_prepared_dictionary(frame, decoder_dict())
+    })
+}
+
+// Tiny spells can come out of zstd larger than they went in; those are stored
+// raw behind RAW_TAG instead.
+fn or_raw(bytes: &[u8], compressed: Vec<u8>) -> Vec<u8> {
+    if compressed.len() <= bytes.len() + 1 {
+        return compressed;
+    }
+
+    let mut raw = Vec::with_capacity(bytes.len() + 1);
+    raw.push(RAW_TAG);
+    raw.extend_from_slice(bytes);
+    raw
+}
+
+pub fn compress_with_dict(bytes: &[u8], dict: &[u8]) -> std::io::Result<Vec<u8>> {
+    let compressed = zstd::bulk::Compressor::with_dictionary(LEVEL, dict)?.compress(bytes)?;
+    Ok(or_raw(bytes, compressed))
+}
+
+pub fn decompress_with_dict(bytes: &[u8], dict: &[u8]) -> Result<Vec<u8>, Error> {
+    unwrap_payload(bytes, &|frame| {
+        zstd::stream::Decoder::with_dictionary(frame, dict)
+    })
+}
+
+fn unwrap_payload<'a, R: std::io::Read>(
+    bytes: &'a [u8],
+    decoder: &dyn Fn(&'a [u8]) -> std::io::Result<R>,
+) -> Result<Vec<u8>, Error> {
+    match bytes.split_first() {
+        Some((&RAW_TAG, raw)) => return Ok(raw.to_vec()),
+        Some((&CHECKSUM_TAG, rest)) => {
+            let [hi, lo, payload @ ..] = rest else {
+                return Err(DecodeError::UnexpectedEof.into());
+            };
+            let expected = u16::from_be_bytes([*hi, *lo]);
+            let actual = crc16(payload);
+            if expected != actual {
+                return Err(DecodeError::ChecksumMismatch { expected, actual }.into());
+            }
+            return unwrap_payload(payload, decoder);
+        }
+        _ => {}
+    }
+
+    let mut dest = Vec::new();
+    std::io::copy(&mut decoder(bytes)?, &mut dest)?;
+
+    Ok(dest)
+}
+
+/// The compression layer under the url-safe strings, for callers that store
+/// spells somewhere the default zstd framing does not suit. Both sides of a
+/// string must use the same codec; nothing in the output says which one made it.
+pub trait Codec {
+    fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error>;
+    fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// What `compress` and `decompress` do: zstd with the embedded dictionary.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZstdDictCodec;
+
+impl Codec for ZstdDictCodec {
+    fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(compress(bytes)?)
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        decompress(bytes)
+    }
+}
+
+/// Passes bytes through untouched.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullCodec;
+
+impl Codec for NullCodec {
+    fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(bytes.to_vec())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl Spell {
+    /// Compressed size over the binary size, not the SNBT, whose length says
+    /// more about formatting than about the spell. Tiny spells stored raw come
+    /// out slightly above 1.
+    pub fn compression_ratio(&self) -> Result<f64, Error> {
+        let bin = self.bin()?;
+        Ok(compress(&bin)?.len() as f64 / bin.len() as f64)
+    }
+
+    pub fn compressed_len(&self) -> Result<usize, Error> {
+        Ok(compress(&self.bin()?)?.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn sample() -> Spell {
+        spell(
+            "sample",
+            vec![
+                with_params(piece("psi:trick_blink", 1, 0), &[("_distance", 3)]),
+                piece("psi:selector_caster", 0, 0),
+            ],
+        )
+    }
+
+    #[test]
+    fn compressed_len_is_the_compressed_binary_length() {
+        let spell = sample();
+        let compressed = compress(&spell.bin().unwrap()).unwrap();
+        assert_eq!(spell.compressed_len().unwrap(), compressed.len());
+    }
+
+    #[test]
+    fn custom_dictionary_round_trips() {
+        let bin = sample().bin().unwrap();
+        let dict = b"psi:trick_blink psi:selector_caster _distance".repeat(8);
+        let compressed = compress_with_dict(&bin, &dict).unwrap();
+        assert_eq!(decompress_with_dict(&compressed, &dict).unwrap(), bin);
+    }
+
+    #[test]
+    fn output_larger_than_the_input_is_stored_raw() {
+        let bin = b"s\0]".to_vec();
+        assert_eq!(or_raw(&bin, vec![0x28; 4]), vec![0x28; 4]);
+        let raw = or_raw(&bin, vec![0x28; 5]);
+        assert_eq!(raw, b"\0s\0]");
+        assert_eq!(decompress(&raw).unwrap(), bin);
+    }
+
+    #[test]
+    fn crc16_matches_the_ccitt_false_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    // A checksum over a raw payload, so the tag is exercised without zstd.
+    fn checked_raw(bin: &[u8]) -> Vec<u8> {
+        let mut payload = vec![RAW_TAG];
+        payload.extend_from_slice(bin);
+        let mut checked = vec![CHECKSUM_TAG];
+        checked.extend_from_slice(&crc16(&payload).to_be_bytes());
+        checked.extend_from_slice(&payload);
+        checked
+    }
+
+    #[test]
+    fn checksum_tag_round_trips() {
+        let bin = b"s\0]".to_vec();
+        assert_eq!(decompress(&checked_raw(&bin)).unwrap(), bin);
+    }
+
+    #[test]
+    fn checksum_tag_catches_a_changed_byte() {
+        let mut checked = checked_raw(b"s\0]");
+        let expected = u16::from_be_bytes([checked[1], checked[2]]);
+        *checked.last_mut().unwrap() = b'[';
+        let actual = crc16(&checked[3..]);
+        assert!(matches!(
+            decompress(&checked),
+            Err(Error::Decode(DecodeError::ChecksumMismatch { expected: e, actual: a }))
+                if (e, a) == (expected, actual)
+        ));
+    }
+
+    #[test]
+    fn checksum_tag_without_its_checksum_is_truncated() {
+        assert!(matches!(
+            decompress(&[CHECKSUM_TAG, 0x12]),
+            Err(Error::Decode(DecodeError::UnexpectedEof))
+        ));
+    }
+
+    #[test]
+    fn compress_checked_prefixes_the_plain_output() {
+        let bin = sample().bin().unwrap();
+        let checked = compress_checked(&bin).unwrap();
+        assert_eq!(checked[0], CHECKSUM_TAG);
+        assert_eq!(checked[3..], compress(&bin).unwrap());
+        assert_eq!(decompress(&checked).unwrap(), bin);
+    }
+
+    #[test]
+    fn compression_ratio_compares_against_the_binary() {
+        let spell = sample();
+        let ratio = spell.compression_ratio().unwrap();
+        let expected = spell.compressed_len().unwrap() as f64 / spell.bin().unwrap().len() as f64;
+        assert_eq!(ratio, expected);
+    }
+
+    #[test]
+    fn dictionaries_are_shared_across_threads() {
+        let bin = sample().bin().unwrap();
+        let expected = compress(&bin).unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let bin = bin.clone();
+                std::thread::spawn(move || {
+                    let compressed = compress(&bin).unwrap();
+                    let decompressed = decompress(&compressed).unwrap();
+                    (compressed, decompressed)
+                })
+            })
+            .collect();
+        for thread in threads {
+            let (compressed, decompressed) = thread.join().unwrap();
+            assert_eq!((compressed, decompressed), (expected.clone(), bin.clone()));
+        }
+        assert!(std::ptr::eq(encoder_dict(), encoder_dict()));
+    }
+
+    #[test]
+    fn null_codec_passes_bytes_through() {
+        let bin = sample().bin().unwrap();
+        let codec: &dyn Codec = &NullCodec;
+        assert_eq!(codec.compress(&bin).unwrap(), bin);
+        assert_eq!(codec.decompress(&bin).unwrap(), bin);
+    }
+
+    #[test]
+    fn zstd_codec_is_the_default_compression() {
+        let bin = sample().bin().unwrap();
+        let codec: &dyn Codec = &ZstdDictCodec;
+        let compressed = codec.compress(&bin).unwrap();
+        assert_eq!(compressed, compress(&bin).unwrap());
+        assert_eq!(codec.decompress(&compressed).unwrap(), bin);
+    }
+}
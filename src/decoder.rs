@@ -0,0 +1,153 @@
+use alloc::vec::Vec;
+
+use crate::{
+    borrowed::{read_preamble, ModRef, PieceRef},
+    format::{self, Layout, BUILTIN_PARAMS},
+    reader::Reader,
+    DecodeError, DecodeOptions,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeEvent<'a> {
+    Name(&'a str),
+    Mod(ModRef<'a>),
+    Piece(PieceRef<'a>),
+}
+
+/// Pull-based decoding for scanning large dumps: the name, then each mod, then
+/// each piece as it is read. The mods and param table are read with the name,
+/// as `decode` reports their errors before any piece's, so only the pieces
+/// are never collected. The events
+/// of one spell carry the same data, in the same order, as `Spell::decode`.
+/// Ends after the first error, which is the one `decode` would give.
+pub struct SpellDecoder<'a> {
+    data: &'a [u8],
+    options: DecodeOptions,
+    started: bool,
+    mods: alloc::vec::IntoIter<ModRef<'a>>,
+    body: Option<(Reader<'a>, Layout<'a>)>,
+    pieces: usize,
+}
+
+impl<'a> SpellDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::with_options(data, DecodeOptions::default())
+    }
+
+    pub fn with_options(data: &'a [u8], options: DecodeOptions) -> Self {
+        Self {
+            data,
+            options,
+            started: false,
+            mods: Vec::new().into_iter(),
+            body: None,
+            pieces: 0,
+        }
+    }
+
+    fn fail(&mut self, err: DecodeError) -> Option<Result<DecodeEvent<'a>, DecodeError>> {
+        self.body = None;
+        Some(Err(err))
+    }
+}
+
+impl<'a> Iterator for SpellDecoder<'a> {
+    type Item = Result<DecodeEvent<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            return match read_preamble(self.data, &BUILTIN_PARAMS) {
+                Ok(preamble) => {
+                    self.mods = preamble.mods.into_iter();
+                    self.body = Some((preamble.reader, preamble.layout));
+                    Some(Ok(DecodeEvent::Name(preamble.name)))
+                }
+                Err(err) => self.fail(err),
+            };
+        }
+        if let Some(m) = self.mods.next() {
+            return Some(Ok(DecodeEvent::Mod(m)));
+        }
+
+        let (reader, layout) = self.body.as_mut()?;
+        if reader.is_empty() {
+            self.body = None;
+            return None;
+        }
+        if self.pieces == self.options.max_pieces {
+            let max = self.options.max_pieces;
+            return self.fail(DecodeError::TooManyPieces { max });
+        }
+
+        let offset = reader.pos();
+        match format::decode_piece(reader, layout, self.options, &mut |_, _| {}) {
+            Ok(piece) => {
+                self.pieces += 1;
+                Some(Ok(DecodeEvent::Piece(piece)))
+            }
+            Err(DecodeError::UnexpectedEof) => self.fail(DecodeError::TrailingData { offset }),
+            Err(err) => self.fail(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn events_rebuild_the_decoded_spell() {
+        let mut spell = spell(
+            "events",
+            vec![
+                piece("psi:selector_caster", 0, 0),
+                with_params(piece("psi:trick_debug", 1, 0), &[("_target", 3)]),
+            ],
+        );
+        spell.mods = vec![crate::Mod {
+            name: "psi".to_string(),
+            version: "r1.1".to_string(),
+        }];
+        spell.pieces[1].data.comment = Some("say it".to_string());
+        let bin = spell.bin().unwrap();
+
+        let mut rebuilt = crate::SpellRef {
+            name: "",
+            mods: Vec::new(),
+            pieces: Vec::new(),
+        };
+        for event in SpellDecoder::new(&bin) {
+            match event.unwrap() {
+                DecodeEvent::Name(name) => rebuilt.name = name,
+                DecodeEvent::Mod(m) => rebuilt.mods.push(m),
+                DecodeEvent::Piece(piece) => rebuilt.pieces.push(piece),
+            }
+        }
+        assert_eq!(rebuilt.into_owned(), crate::Spell::decode(&bin).unwrap());
+    }
+
+    #[test]
+    fn decoding_ends_after_the_error_decode_gives() {
+        let bin = spell("cut", vec![piece("psi:connector", 0, 0)])
+            .bin()
+            .unwrap();
+        let cut = [bin.as_slice(), b"\x10trick"].concat();
+        let events: Vec<_> = SpellDecoder::new(&cut).collect();
+        let expected = crate::Spell::decode(&cut).unwrap_err();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2], Err(expected));
+
+        let options = DecodeOptions {
+            max_pieces: 0,
+            ..DecodeOptions::default()
+        };
+        let events: Vec<_> = SpellDecoder::with_options(&bin, options).collect();
+        assert_eq!(
+            events.last(),
+            Some(&Err(DecodeError::TooManyPieces { max: 0 }))
+        );
+        assert_eq!(events.len(), 2);
+    }
+}
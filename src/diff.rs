@@ -0,0 +1,222 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{Mod, Piece, Spell, SpellData};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpellDiff {
+    pub name: Option<(String, String)>,
+    pub mods_added: Vec<Mod>,
+    pub mods_removed: Vec<Mod>,
+    pub pieces_added: Vec<Piece>,
+    pub pieces_removed: Vec<Piece>,
+    pub pieces_moved: Vec<PieceMove>,
+    pub pieces_changed: Vec<PieceChange>,
+}
+
+impl SpellDiff {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceMove {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+    pub data: SpellData,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PieceChange {
+    pub position: (u8, u8),
+    pub key: Option<(String, String)>,
+    pub params: Vec<ParamChange>,
+    pub constant: Option<(Option<String>, Option<String>)>,
+    pub comment: Option<(Option<String>, Option<String>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamChange {
+    pub name: String,
+    pub old: Option<u8>,
+    pub new: Option<u8>,
+}
+
+fn changed<T: Clone + PartialEq>(old: &T, new: &T) -> Option<(T, T)> {
+    (old != new).then(|| (old.clone(), new.clone()))
+}
+
+fn diff_data(position: (u8, u8), old: &SpellData, new: &SpellData) -> PieceChange {
+    let mut names = BTreeMap::new();
+    for (name, side) in old.params.iter().flatten() {
+        names.entry(name.as_str()).or_insert((None, None)).0 = Some(*side);
+    }
+    for (name, side) in new.params.iter().flatten() {
+        names.entry(name.as_str()).or_insert((None, None)).1 = Some(*side);
+    }
+
+    let params = names
+        .into_iter()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(name, (old, new))| ParamChange {
+            name: name.to_string(),
+            old,
+            new,
+        })
+        .collect();
+
+    PieceChange {
+        position,
+        key: changed(&old.key, &new.key),
+        params,
+        constant: changed(&old.constant, &new.constant),
+        comment: changed(&old.comment, &new.comment),
+    }
+}
+
+impl Spell {
+    pub fn diff(&self, other: &Spell) -> SpellDiff {
+        let mut diff = SpellDiff {
+            name: changed(&self.name, &other.name),
+            mods_added: other
+                .mods
+                .iter()
+                .filter(|m| !self.mods.contains(m))
+                .cloned()
+                .collect(),
+            mods_removed: self
+                .mods
+                .iter()
+                .filter(|m| !other.mods.contains(m))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+
+        let old: BTreeMap<_, _> = self.pieces.iter().map(|p| ((p.x, p.y), p)).collect();
+        let new: BTreeMap<_, _> = other.pieces.iter().map(|p| ((p.x, p.y), p)).collect();
+
+        let mut removed = Vec::new();
+        for (position, piece) in &old {
+            match new.get(position) {
+                Some(other) if other.data != piece.data => {
+                    diff.pieces_changed
+                        .push(diff_data(*position, &piece.data, &other.data))
+                }
+                Some(_) => {}
+                None => removed.push(*piece),
+            }
+        }
+        let mut added: Vec<_> = new
+            .iter()
+            .filter(|(position, _)| !old.contains_key(position))
+            .map(|(_, piece)| *piece)
+            .collect();
+
+        // A piece that disappeared from one cell and reappeared unchanged in
+        // another is reported as a move rather than a removal plus an addition.
+        for piece in removed {
+            match added.iter().position(|a| a.data == piece.data) {
+                Some(index) => {
+                    let to = added.remove(index);
+                    diff.pieces_moved.push(PieceMove {
+                        from: (piece.x, piece.y),
+                        to: (to.x, to.y),
+                        data: piece.data.clone(),
+                    });
+                }
+                None => diff.pieces_removed.push(piece.clone()),
+            }
+        }
+        diff.pieces_added = added.into_iter().cloned().collect();
+
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn identical_spells_have_an_empty_diff() {
+        let spell = spell("same", vec![piece("psi:selector_caster", 0, 0)]);
+        assert!(spell.diff(&spell.clone()).is_empty());
+    }
+
+    #[test]
+    fn unchanged_piece_in_a_new_cell_is_a_move() {
+        let old = spell("moves", vec![piece("psi:selector_caster", 0, 0)]);
+        let new = spell("moves", vec![piece("psi:selector_caster", 3, 2)]);
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.pieces_moved,
+            vec![PieceMove {
+                from: (0, 0),
+                to: (3, 2),
+                data: piece("psi:selector_caster", 0, 0).data,
+            }]
+        );
+        assert!(diff.pieces_added.is_empty() && diff.pieces_removed.is_empty());
+    }
+
+    #[test]
+    fn edited_piece_reports_each_changed_field() {
+        let old = spell(
+            "edits",
+            vec![with_params(
+                piece("psi:operator_sum", 1, 1),
+                &[("_number1", 1), ("_number2", 2)],
+            )],
+        );
+        let mut edited = with_params(
+            piece("psi:operator_sum", 1, 1),
+            &[("_number1", 1), ("_number3", 4)],
+        );
+        edited.data.comment = Some("sum".to_string());
+        let new = spell("edits", vec![edited]);
+
+        assert_eq!(
+            old.diff(&new).pieces_changed,
+            vec![PieceChange {
+                position: (1, 1),
+                key: None,
+                params: vec![
+                    ParamChange {
+                        name: "_number2".to_string(),
+                        old: Some(2),
+                        new: None,
+                    },
+                    ParamChange {
+                        name: "_number3".to_string(),
+                        old: None,
+                        new: Some(4),
+                    },
+                ],
+                constant: None,
+                comment: Some((None, Some("sum".to_string()))),
+            }]
+        );
+    }
+
+    #[test]
+    fn different_piece_in_a_new_cell_is_an_addition_and_a_removal() {
+        let mut old = spell("swap", vec![piece("psi:selector_caster", 0, 0)]);
+        old.mods = vec![Mod {
+            name: "psi".to_string(),
+            version: "1".to_string(),
+        }];
+        let new = spell("renamed", vec![piece("psi:trick_debug", 1, 0)]);
+        let diff = old.diff(&new);
+        assert_eq!(diff.name, Some(("swap".to_string(), "renamed".to_string())));
+        assert_eq!(diff.mods_removed, old.mods);
+        assert_eq!(diff.pieces_added, new.pieces);
+        assert_eq!(diff.pieces_removed, old.pieces);
+        assert!(diff.pieces_moved.is_empty());
+    }
+}
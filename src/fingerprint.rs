@@ -0,0 +1,197 @@
+#[cfg(feature = "content-hash")]
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+#[cfg(feature = "content-hash")]
+use crate::EncodeError;
+use crate::{format, Spell, SpellParams};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_str(&mut self, s: &str) {
+        self.write(s.as_bytes());
+        self.write(&[0]);
+    }
+}
+
+// What the fingerprint covers of one piece: its position relative to the
+// bounding box, its key as stored, and the body the binary form would write.
+#[derive(PartialEq, Eq)]
+struct Shape<'a> {
+    x: u8,
+    y: u8,
+    key: &'a [u8],
+    params: Option<&'a SpellParams>,
+    constant: Option<&'a str>,
+}
+
+impl Spell {
+    fn shapes(&self) -> Vec<Shape<'_>> {
+        let min_x = self.pieces.iter().map(|p| p.x).min().unwrap_or(0);
+        let min_y = self.pieces.iter().map(|p| p.y).min().unwrap_or(0);
+
+        self.pieces_in_grid_order()
+            .map(|piece| {
+                let data = &piece.data;
+                let params = format::params_to_encode(data);
+                Shape {
+                    x: piece.x - min_x,
+                    y: piece.y - min_y,
+                    key: format::stored_key(&data.key),
+                    params,
+                    constant: data.constant.as_deref().filter(|_| params.is_none()),
+                }
+            })
+            .collect()
+    }
+
+    /// FNV-1a over the piece layout relative to its bounding box, so the value is
+    /// stable across platforms and releases. The spell name, mods and comments do
+    /// not contribute, and neither does writing `psi:` on a key or leaving it
+    /// implicit.
+    pub fn structural_fingerprint(&self) -> u64 {
+        let mut hash = Fnv(FNV_OFFSET);
+        for shape in self.shapes() {
+            hash.write(&[shape.x, shape.y]);
+            hash.write(shape.key);
+            hash.write(&[0]);
+
+            if let Some(params) = shape.params {
+                hash.write(b"p");
+                for (name, side) in params {
+                    hash.write_str(name);
+                    hash.write(&[*side]);
+                }
+            } else if let Some(constant) = shape.constant {
+                hash.write(b"c");
+                hash.write_str(constant);
+            } else {
+                hash.write(b"n");
+            }
+        }
+
+        hash.0
+    }
+}
+
+#[cfg(feature = "content-hash")]
+impl Spell {
+    /// SHA-256 of the binary encoding, with each comment trimmed and dropped if
+    /// nothing is left of it. Unlike the structural fingerprint this covers the
+    /// name, mods, absolute positions and comment text, so it only matches
+    /// spells that would share the same string modulo comment whitespace.
+    pub fn content_hash(&self) -> Result<[u8; 32], EncodeError> {
+        use sha2::{Digest, Sha256};
+
+        let mut spell = self.clone();
+        for piece in &mut spell.pieces {
+            piece.data.comment = piece
+                .data
+                .comment
+                .as_deref()
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(str::to_owned);
+        }
+
+        Ok(Sha256::digest(spell.bin()?).into())
+    }
+}
+
+/// Drops consecutive spells with the same structure. The fingerprint only
+/// narrows the comparison down, so two layouts that collide on it are both kept.
+pub fn dedup_spells(spells: &mut Vec<Spell>) {
+    spells.dedup_by(|spell, previous| {
+        spell.structural_fingerprint() == previous.structural_fingerprint()
+            && spell.shapes() == previous.shapes()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn named(name: &str, dx: u8) -> Spell {
+        spell(
+            name,
+            vec![
+                piece("psi:selector_caster", dx, 0),
+                with_params(piece("psi:trick_debug", dx + 1, 0), &[("_target", 3)]),
+            ],
+        )
+    }
+
+    #[test]
+    fn fingerprint_ignores_name_and_offset() {
+        assert_eq!(
+            named("a", 0).structural_fingerprint(),
+            named("b", 4).structural_fingerprint()
+        );
+    }
+
+    #[test]
+    fn fingerprint_sees_params() {
+        let mut changed = named("a", 0);
+        changed.pieces[1] = with_params(piece("psi:trick_debug", 1, 0), &[("_target", 4)]);
+        assert_ne!(
+            named("a", 0).structural_fingerprint(),
+            changed.structural_fingerprint()
+        );
+    }
+
+    #[test]
+    fn fingerprint_ignores_the_implicit_psi_prefix() {
+        let bare = spell("a", vec![piece("connector", 0, 0)]);
+        let prefixed = spell("a", vec![piece("psi:connector", 0, 0)]);
+        assert_eq!(bare.bin().unwrap(), prefixed.bin().unwrap());
+        assert_eq!(
+            bare.structural_fingerprint(),
+            prefixed.structural_fingerprint()
+        );
+
+        let mut spells = vec![bare, prefixed];
+        dedup_spells(&mut spells);
+        assert_eq!(spells.len(), 1);
+    }
+
+    #[test]
+    fn dedup_drops_only_consecutive_repeats() {
+        let other = spell("other", vec![piece("psi:selector_caster", 0, 0)]);
+        let mut spells = vec![named("a", 0), named("b", 2), other.clone(), named("c", 0)];
+        dedup_spells(&mut spells);
+        assert_eq!(spells, vec![named("a", 0), other, named("c", 0)]);
+    }
+
+    #[cfg(feature = "content-hash")]
+    #[test]
+    fn content_hash_ignores_only_comment_whitespace() {
+        let with_comment = |comment: Option<&str>| {
+            let mut spell = named("a", 0);
+            spell.pieces[0].data.comment = comment.map(str::to_string);
+            spell.content_hash().unwrap()
+        };
+        assert_eq!(with_comment(Some("  note\n")), with_comment(Some("note")));
+        assert_eq!(with_comment(Some(" ")), with_comment(None));
+        assert_ne!(with_comment(Some("note")), with_comment(Some("other")));
+        assert_ne!(
+            named("a", 0).content_hash().unwrap(),
+            named("b", 0).content_hash().unwrap()
+        );
+        assert_ne!(
+            named("a", 0).content_hash().unwrap(),
+            named("a", 1).content_hash().unwrap()
+        );
+    }
+}
@@ -0,0 +1,513 @@
+// The binary spell layout, which `Spell::bin_with_options` and
+// `Spell::decode_borrowed` assemble from the pieces below:
+//
+//   [HEADER_TAG flags]       only when a flag is set
+//   name NUL
+//   mod,version;...]         just `]` without mods
+//   [count (name NUL)*]      with FLAG_PARAM_TABLE
+//   piece*                   until the end of the data
+//
+// A piece is its position (one packed byte, or x and y with
+// FLAG_EXTENDED_POSITIONS), key without its `psi:` prefix NUL, comment NUL,
+// then one of:
+//
+//   NO_PARAMS
+//   CONSTANT constant NUL
+//   count (index | CUSTOM_PARAM name NUL | PARAM_REF table_index) side ...
+
+use alloc::{borrow::Cow, collections::BTreeMap, vec::Vec};
+
+use crate::{
+    borrowed::{ParamOrigin, PieceRef, SpellDataRef},
+    reader::Reader,
+    DecodeError, DecodeOptions, Field, Piece, SpellData, SpellParams,
+};
+
+pub(crate) const BUILTIN_PARAMS: [&str; 43] = [
+    "_target",
+    "_number",
+    "_number1",
+    "_number2",
+    "_number3",
+    "_number4",
+    "_vector1",
+    "_vector2",
+    "_vector3",
+    "_vector4",
+    "_position",
+    "_min",
+    "_max",
+    "_power",
+    "_x",
+    "_y",
+    "_z",
+    "_radius",
+    "_distance",
+    "_time",
+    "_base",
+    "_ray",
+    "_vector",
+    "_axis",
+    "_angle",
+    "_pitch",
+    "_instrument",
+    "_volume",
+    "_list1",
+    "_list2",
+    "_list",
+    "_direction",
+    "_from1",
+    "_from2",
+    "_to1",
+    "_to2",
+    "_root",
+    "_toggle",
+    "_mask",
+    "_channel",
+    "_slot",
+    "_ray_end",
+    "_ray_start",
+];
+
+pub(crate) const MAX_PARAMS: usize = 253;
+// Largest coordinate that fits the packed one-byte position. Spells reaching
+// past it are written in extended mode, with a full byte per coordinate.
+pub(crate) const MAX_COORD: u8 = 0b1111;
+
+// No spell name starts with this control byte, so older spells without a
+// header still decode. The byte after it holds the FLAG_* bits.
+pub(crate) const HEADER_TAG: u8 = 1;
+pub(crate) const FLAG_EXTENDED_POSITIONS: u8 = 1 << 0;
+pub(crate) const FLAG_PARAM_TABLE: u8 = 1 << 1;
+
+pub(crate) const NO_PARAMS: u8 = 254;
+pub(crate) const CONSTANT: u8 = 255;
+// In place of a BUILTIN_PARAMS index, for a param name spelled out in full.
+pub(crate) const CUSTOM_PARAM: u8 = 255;
+// Only with FLAG_PARAM_TABLE; otherwise 254 is just an unknown builtin.
+pub(crate) const PARAM_REF: u8 = 254;
+
+// What the header flags announce, shared by every piece of one spell.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Layout<'a> {
+    pub(crate) extended: bool,
+    pub(crate) param_table: Option<Vec<&'a str>>,
+    // What builtin indices decode to. Encoding always uses BUILTIN_PARAMS.
+    pub(crate) builtins: &'a [&'a str],
+}
+
+impl<'a> Layout<'a> {
+    pub(crate) fn of(pieces: &'a [Piece]) -> Self {
+        let extended = pieces.iter().any(|p| p.x > MAX_COORD || p.y > MAX_COORD);
+        Self {
+            extended,
+            param_table: param_table(pieces, extended),
+            builtins: &BUILTIN_PARAMS,
+        }
+    }
+
+    pub(crate) fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.extended {
+            flags |= FLAG_EXTENDED_POSITIONS;
+        }
+        if self.param_table.is_some() {
+            flags |= FLAG_PARAM_TABLE;
+        }
+        flags
+    }
+
+    pub(crate) fn position_len(&self) -> usize {
+        if self.extended {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub(crate) fn table_index(&self, name: &str) -> Option<u8> {
+        let table = self.param_table.as_ref()?;
+        table.iter().position(|n| *n == name).map(|i| i as u8)
+    }
+}
+
+// Custom param names repeated often enough to pay for writing them once up
+// front. Inline a name costs its length plus two bytes every time, a table
+// reference two bytes; the table itself costs the name plus its NUL, one count
+// byte, and the header if nothing else needed it.
+fn param_table(pieces: &[Piece], extended: bool) -> Option<Vec<&str>> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for piece in pieces {
+        for name in piece.data.params.iter().flat_map(|p| p.keys()) {
+            if !BUILTIN_PARAMS.contains(&name.as_str()) {
+                *counts.entry(name).or_default() += 1;
+            }
+        }
+    }
+
+    let mut saved = 0;
+    let table: Vec<_> = counts
+        .into_iter()
+        .filter(|(name, count)| (count - 1) * name.len() > 1)
+        .take(u8::MAX as usize)
+        .map(|(name, count)| {
+            saved += (count - 1) * name.len() - 1;
+            name
+        })
+        .collect();
+
+    let overhead = if extended { 1 } else { 3 };
+    (saved > overhead).then_some(table)
+}
+
+// Psi writes a params compound on every piece, empty for constants, so a
+// constant wins over an empty map. Params and a constant both set never
+// reach here; `encode_errors` rejects them.
+pub(crate) fn params_to_encode(data: &SpellData) -> Option<&SpellParams> {
+    data.params
+        .as_ref()
+        .filter(|params| !params.is_empty() || data.constant.is_none())
+}
+
+pub(crate) fn stored_key(key: &str) -> &[u8] {
+    key.strip_prefix("psi:").unwrap_or(key).as_bytes()
+}
+
+pub(crate) fn encode_piece(
+    out: &mut Vec<u8>,
+    piece: &Piece,
+    layout: &Layout,
+    comment: Option<&str>,
+) {
+    let data = &piece.data;
+    let key = stored_key(&data.key);
+
+    if layout.extended {
+        out.push(piece.x);
+        out.push(piece.y);
+    } else {
+        out.push(piece.x << 4 | (piece.y & 0b1111));
+    }
+    out.extend_from_slice(key);
+    out.push(0);
+    if let Some(comment) = comment {
+        out.extend_from_slice(comment.as_bytes());
+    }
+    out.push(0);
+
+    if let Some(params) = params_to_encode(data) {
+        out.push(params.len() as u8);
+        for (key, side) in params {
+            if let Some(pos) = BUILTIN_PARAMS.iter().position(|e| **e == *key) {
+                out.push(pos as u8);
+            } else if let Some(index) = layout.table_index(key) {
+                out.push(PARAM_REF);
+                out.push(index);
+            } else {
+                out.push(CUSTOM_PARAM);
+                out.extend_from_slice(key.as_bytes());
+                out.push(0);
+            }
+            out.push(*side);
+        }
+    } else if let Some(constant) = &data.constant {
+        out.push(CONSTANT);
+        out.extend_from_slice(constant.as_bytes());
+        out.push(0);
+    } else {
+        out.push(NO_PARAMS);
+    }
+}
+
+// `on_param` hears how each param was written, as its byte is read; the name
+// alone cannot tell a builtin from a custom param spelled the same.
+pub(crate) fn decode_piece<'a>(
+    reader: &mut Reader<'a>,
+    layout: &Layout<'a>,
+    options: DecodeOptions,
+    on_param: &mut dyn FnMut(&str, ParamOrigin),
+) -> Result<PieceRef<'a>, DecodeError> {
+    let (x, y) = if layout.extended {
+        (reader.next()?, reader.next()?)
+    } else {
+        let xy = reader.next()?;
+        (xy >> 4, xy & 0b1111)
+    };
+    let key = reader.read_str_until_nul(Field::Key)?;
+    let key = if key.contains(':') {
+        Cow::Borrowed(key)
+    } else {
+        Cow::Owned(format!("psi:{key}"))
+    };
+
+    let comment = reader.read_str_until_nul(Field::Comment)?;
+    let comment = if comment.is_empty() {
+        None
+    } else {
+        Some(comment)
+    };
+
+    let mut params = None;
+    let mut constant = None;
+
+    // A zero count stays `Some` of an empty map, distinct from NO_PARAMS.
+    let ty = reader.next()?;
+    if ty == CONSTANT {
+        constant = Some(reader.read_str_until_nul(Field::Constant)?);
+    } else if ty != NO_PARAMS {
+        let params = params.insert(BTreeMap::new());
+        let len = ty;
+        for _ in 0..len {
+            let type_or_pos = reader.next()?;
+            let (param_key, origin) = if type_or_pos == CUSTOM_PARAM {
+                (
+                    Cow::Borrowed(reader.read_str_until_nul(Field::ParamName)?),
+                    ParamOrigin::Custom,
+                )
+            } else if let (PARAM_REF, Some(table)) = (type_or_pos, &layout.param_table) {
+                let index = reader.next()?;
+                let name = table
+                    .get(index as usize)
+                    .ok_or(DecodeError::UnknownParamRef(index))?;
+                (Cow::Borrowed(*name), ParamOrigin::Interned { index })
+            } else if let Some(name) = layout.builtins.get(type_or_pos as usize) {
+                (
+                    Cow::Borrowed(*name),
+                    ParamOrigin::Builtin { index: type_or_pos },
+                )
+            } else if options.lenient_params {
+                (
+                    Cow::Owned(format!("_builtin_{type_or_pos}")),
+                    ParamOrigin::Builtin { index: type_or_pos },
+                )
+            } else {
+                return Err(DecodeError::UnknownParam(type_or_pos));
+            };
+            on_param(&param_key, origin);
+
+            let side = reader.next()?;
+            params.insert(param_key, side);
+        }
+    }
+
+    let data = SpellDataRef {
+        key,
+        params,
+        constant,
+        comment,
+    };
+
+    Ok(PieceRef { data, x, y })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn encode(piece: &Piece, layout: &Layout) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_piece(&mut out, piece, layout, piece.data.comment.as_deref());
+        out
+    }
+
+    fn decode(bytes: &[u8], layout: &Layout) -> Piece {
+        let mut reader = Reader::new(bytes);
+        let piece = decode_piece(
+            &mut reader,
+            layout,
+            DecodeOptions::default(),
+            &mut |_, _| {},
+        )
+        .unwrap();
+        assert!(reader.is_empty(), "piece left bytes behind");
+        crate::SpellRef {
+            name: "",
+            mods: vec![],
+            pieces: vec![piece],
+        }
+        .into_owned()
+        .pieces
+        .remove(0)
+    }
+
+    fn compact() -> Layout<'static> {
+        Layout {
+            builtins: &BUILTIN_PARAMS,
+            ..Layout::default()
+        }
+    }
+
+    #[test]
+    fn piece_states_have_distinct_encodings() {
+        let mut constant = piece("psi:constant_number", 1, 2);
+        constant.data.constant = Some("42".to_string());
+        let mut commented = with_params(piece("psi:trick_debug", 1, 2), &[("_target", 4)]);
+        commented.data.comment = Some("hi".to_string());
+        let cases: [(Piece, &[u8]); 4] = [
+            (
+                piece("psi:selector_caster", 1, 2),
+                b"\x12selector_caster\0\0\xfe",
+            ),
+            (
+                with_params(piece("psi:operator_sum", 1, 2), &[]),
+                b"\x12operator_sum\0\0\x00",
+            ),
+            (constant, b"\x12constant_number\0\0\xff42\0"),
+            (commented, b"\x12trick_debug\0hi\0\x01\x00\x04"),
+        ];
+        for (piece, bytes) in cases {
+            assert_eq!(encode(&piece, &compact()), bytes, "{piece:?}");
+            assert_eq!(decode(bytes, &compact()), piece);
+        }
+    }
+
+    #[test]
+    fn empty_params_with_a_constant_encode_as_the_constant() {
+        let mut constant = with_params(piece("psi:constant_number", 0, 0), &[]);
+        constant.data.constant = Some("1".to_string());
+        assert_eq!(
+            encode(&constant, &compact()),
+            b"\x00constant_number\0\0\xff1\0"
+        );
+    }
+
+    #[test]
+    fn extended_positions_take_a_byte_per_coordinate() {
+        let layout = Layout {
+            extended: true,
+            ..compact()
+        };
+        let far = piece("psi:connector", 200, 3);
+        let bytes = encode(&far, &layout);
+        assert_eq!(bytes, b"\xc8\x03connector\0\0\xfe");
+        assert_eq!(decode(&bytes, &layout), far);
+    }
+
+    #[test]
+    fn custom_params_use_the_table_when_listed() {
+        let layout = Layout {
+            param_table: Some(vec!["_other", "_shared"]),
+            ..compact()
+        };
+        let piece = with_params(
+            piece("psi:connector", 0, 0),
+            &[("_shared", 1), ("_unlisted", 2)],
+        );
+        let bytes = encode(&piece, &layout);
+        assert_eq!(
+            bytes,
+            b"\x00connector\0\0\x02\xfe\x01\x01\xff_unlisted\0\x02"
+        );
+        assert_eq!(decode(&bytes, &layout), piece);
+    }
+
+    #[test]
+    fn param_ref_past_the_table_is_rejected() {
+        let layout = Layout {
+            param_table: Some(vec!["_shared"]),
+            ..compact()
+        };
+        let mut reader = Reader::new(b"\x00connector\0\0\x01\xfe\x01\x01");
+        assert_eq!(
+            decode_piece(
+                &mut reader,
+                &layout,
+                DecodeOptions::default(),
+                &mut |_, _| {}
+            ),
+            Err(DecodeError::UnknownParamRef(1))
+        );
+    }
+
+    #[test]
+    fn param_table_only_pays_for_names_it_shortens() {
+        let shared = |x| with_params(piece("psi:connector", x, 0), &[("_long_custom", 1)]);
+        let pieces = vec![shared(0), shared(1), shared(2)];
+        assert_eq!(param_table(&pieces, false), Some(vec!["_long_custom"]));
+        // Written once, a name gains nothing from the table.
+        assert_eq!(param_table(&pieces[..1], false), None);
+        // Builtins always have their own index.
+        let builtin = |x| with_params(piece("psi:connector", x, 0), &[("_ray_start", 1)]);
+        assert_eq!(
+            param_table(&[builtin(0), builtin(1), builtin(2)], false),
+            None
+        );
+    }
+
+    #[test]
+    fn index_past_the_builtins_is_rejected() {
+        let index = BUILTIN_PARAMS.len() as u8;
+        let bytes = [b"\x00connector\0\0\x01".as_slice(), &[index, 1]].concat();
+        let mut reader = Reader::new(&bytes);
+        assert_eq!(
+            decode_piece(
+                &mut reader,
+                &compact(),
+                DecodeOptions::default(),
+                &mut |_, _| {}
+            ),
+            Err(DecodeError::UnknownParam(index))
+        );
+    }
+
+    // What the fuzz target checks, over every truncation and single-byte change
+    // of one spell that uses each part of the format.
+    #[test]
+    fn corrupt_input_errors_instead_of_panicking() {
+        let mut constant = piece("psi:constant_number", 0, 0);
+        constant.data.constant = Some("5".to_string());
+        let shared = |x| with_params(piece("psi:connector", x, 20), &[("_long_custom", 1)]);
+        let bin = spell("fuzz", vec![constant, shared(1), shared(2), shared(3)])
+            .bin()
+            .unwrap();
+
+        for len in 0..bin.len() {
+            let _ = crate::Spell::decode(&bin[..len]);
+        }
+        for i in 0..bin.len() {
+            for byte in [0, 1, 0x7f, 0xfe, 0xff] {
+                let mut corrupt = bin.clone();
+                corrupt[i] = byte;
+                let _ = crate::Spell::decode(&corrupt);
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_decode_names_unknown_builtins() {
+        let bytes = b"\x00connector\0\0\x01\xc8\x01";
+        let lenient = DecodeOptions {
+            lenient_params: true,
+            ..DecodeOptions::default()
+        };
+        let mut origins = Vec::new();
+        let piece = decode_piece(
+            &mut Reader::new(bytes),
+            &compact(),
+            lenient,
+            &mut |name, origin| origins.push((name.to_string(), origin)),
+        )
+        .unwrap();
+        let params = piece.data.params.unwrap();
+        assert_eq!(params.get("_builtin_200"), Some(&1));
+        assert_eq!(
+            origins,
+            [(
+                "_builtin_200".to_string(),
+                ParamOrigin::Builtin { index: 200 }
+            )]
+        );
+    }
+
+    #[test]
+    fn short_keys_store_whatever_follows_psi() {
+        assert_eq!(stored_key("psi:"), b"");
+        assert_eq!(stored_key("ps"), b"ps");
+        assert_eq!(stored_key(""), b"");
+
+        let empty = piece("psi:", 0, 0);
+        assert_eq!(encode(&empty, &compact()), b"\x00\0\0\xfe");
+        assert_eq!(decode(b"\x00\0\0\xfe", &compact()), empty);
+    }
+}
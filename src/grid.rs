@@ -0,0 +1,202 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use crate::{Mod, Piece, Spell, SpellData};
+
+/// A spell laid out as rows of cells, from (0, 0) to its furthest piece, for
+/// O(1) lookups by position. Keeps the name and mods so `into_spell` gives back
+/// the same spell, with pieces in grid order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Grid {
+    pub name: String,
+    pub mods: Vec<Mod>,
+    width: usize,
+    height: usize,
+    cells: Vec<Option<SpellData>>,
+}
+
+impl Grid {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: u8, y: u8) -> Option<&SpellData> {
+        self.index(x, y).and_then(|i| self.cells[i].as_ref())
+    }
+
+    pub fn get_mut(&mut self, x: u8, y: u8) -> Option<&mut SpellData> {
+        self.index(x, y).and_then(|i| self.cells[i].as_mut())
+    }
+
+    /// Grows the grid as needed to reach (x, y). Returns whatever was there.
+    pub fn set(&mut self, x: u8, y: u8, data: Option<SpellData>) -> Option<SpellData> {
+        let (x, y) = (x as usize, y as usize);
+        if x >= self.width || y >= self.height {
+            let (width, height) = (self.width.max(x + 1), self.height.max(y + 1));
+            let mut cells = vec![None; width * height];
+            for (i, cell) in core::mem::take(&mut self.cells).into_iter().enumerate() {
+                cells[(i / self.width) * width + i % self.width] = cell;
+            }
+            (self.width, self.height, self.cells) = (width, height, cells);
+        }
+        core::mem::replace(&mut self.cells[y * self.width + x], data)
+    }
+
+    pub fn into_spell(self) -> Spell {
+        let width = self.width;
+        let pieces = self
+            .cells
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, data)| {
+                Some(Piece {
+                    data: data?,
+                    x: (i % width) as u8,
+                    y: (i / width) as u8,
+                })
+            })
+            .collect();
+
+        Spell {
+            name: self.name,
+            mods: self.mods,
+            pieces,
+        }
+    }
+
+    fn index(&self, x: u8, y: u8) -> Option<usize> {
+        let (x, y) = (x as usize, y as usize);
+        (x < self.width && y < self.height).then(|| y * self.width + x)
+    }
+}
+
+impl Spell {
+    /// Of two pieces sharing a cell, the later one in `pieces` wins.
+    pub fn to_grid(&self) -> Grid {
+        let width = self
+            .pieces
+            .iter()
+            .map(|p| p.x as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let height = self
+            .pieces
+            .iter()
+            .map(|p| p.y as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut grid = Grid {
+            name: self.name.clone(),
+            mods: self.mods.clone(),
+            width,
+            height,
+            cells: vec![None; width * height],
+        };
+        for piece in &self.pieces {
+            grid.set(piece.x, piece.y, Some(piece.data.clone()));
+        }
+        grid
+    }
+
+    /// Row-major, whatever order `pieces` happens to be in.
+    pub fn pieces_in_grid_order(&self) -> impl Iterator<Item = &Piece> {
+        let mut pieces: Vec<_> = self.pieces.iter().collect();
+        pieces.sort_by_key(|p| (p.y, p.x));
+        pieces.into_iter()
+    }
+
+    /// Keyed by y, each row ordered by x. Empty rows are left out.
+    pub fn rows(&self) -> BTreeMap<u8, Vec<&Piece>> {
+        let mut rows: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for piece in self.pieces_in_grid_order() {
+            rows.entry(piece.y).or_default().push(piece);
+        }
+        rows
+    }
+
+    /// Keyed by x, each column ordered by y. Empty columns are left out.
+    pub fn columns(&self) -> BTreeMap<u8, Vec<&Piece>> {
+        let mut columns: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for piece in self.pieces_in_grid_order() {
+            columns.entry(piece.x).or_default().push(piece);
+        }
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn scattered() -> Spell {
+        spell(
+            "scattered",
+            vec![
+                piece("psi:trick_debug", 2, 1),
+                piece("psi:selector_caster", 0, 1),
+                piece("psi:connector", 2, 0),
+            ],
+        )
+    }
+
+    fn positions<'a>(pieces: impl IntoIterator<Item = &'a Piece>) -> Vec<(u8, u8)> {
+        pieces.into_iter().map(|p| (p.x, p.y)).collect()
+    }
+
+    #[test]
+    fn grid_order_is_row_major() {
+        let spell = scattered();
+        assert_eq!(
+            positions(spell.pieces_in_grid_order()),
+            vec![(2, 0), (0, 1), (2, 1)]
+        );
+    }
+
+    #[test]
+    fn rows_and_columns_skip_empty_lines() {
+        let spell = scattered();
+        let rows: Vec<_> = spell
+            .rows()
+            .into_iter()
+            .map(|(y, row)| (y, positions(row)))
+            .collect();
+        assert_eq!(rows, vec![(0, vec![(2, 0)]), (1, vec![(0, 1), (2, 1)])]);
+        let columns: Vec<_> = spell
+            .columns()
+            .into_iter()
+            .map(|(x, column)| (x, positions(column)))
+            .collect();
+        assert_eq!(columns, vec![(0, vec![(0, 1)]), (2, vec![(2, 0), (2, 1)])]);
+    }
+
+    #[test]
+    fn grid_looks_up_by_position() {
+        let grid = scattered().to_grid();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(
+            grid.get(0, 1).map(|d| d.key.as_str()),
+            Some("psi:selector_caster")
+        );
+        assert_eq!(grid.get(1, 1), None);
+        assert_eq!(grid.get(5, 5), None);
+    }
+
+    #[test]
+    fn grid_grows_and_gives_back_the_spell_in_grid_order() {
+        let mut grid = scattered().to_grid();
+        let old = grid.set(2, 0, None);
+        assert_eq!(old.map(|d| d.key), Some("psi:connector".to_string()));
+        grid.set(4, 3, Some(piece("psi:trick_debug", 0, 0).data));
+        assert_eq!((grid.width(), grid.height()), (5, 4));
+        // Cells keep their place when the grid grows.
+        assert!(grid.get(0, 1).is_some() && grid.get(2, 1).is_some());
+
+        let spell = grid.into_spell();
+        assert_eq!(spell.name, "scattered");
+        assert_eq!(positions(&spell.pieces), vec![(0, 1), (2, 1), (4, 3)]);
+    }
+}
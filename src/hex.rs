@@ -0,0 +1,101 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{Error, Spell};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HexError {
+    OddLength(usize),
+    InvalidDigit { offset: usize, digit: char },
+}
+
+impl core::fmt::Display for HexError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            HexError::OddLength(len) => write!(f, "hex string has odd length {len}"),
+            HexError::InvalidDigit { offset, digit } => {
+                write!(f, "invalid hex digit {digit:?} at offset {offset}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for HexError {}
+
+/// A debugging view of the binary form, not a sharing format: it is twice the
+/// size of the bytes and never compressed.
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+/// Either case is accepted.
+pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, HexError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(HexError::OddLength(hex.len()));
+    }
+
+    // Walks chars rather than bytes so a multi-byte character is reported
+    // whole, at the byte offset it starts at.
+    let mut digits = hex.char_indices().map(|(offset, digit)| {
+        digit
+            .to_digit(16)
+            .map(|d| d as u8)
+            .ok_or(HexError::InvalidDigit { offset, digit })
+    });
+    let mut bytes = Vec::with_capacity(hex.len() / 2);
+    while let Some(high) = digits.next() {
+        let high = high?;
+        let low = digits.next().ok_or(HexError::OddLength(hex.len()))??;
+        bytes.push(high << 4 | low);
+    }
+    Ok(bytes)
+}
+
+pub fn spell_to_hex(spell: &Spell) -> Result<String, Error> {
+    Ok(bytes_to_hex(&spell.bin()?))
+}
+
+pub fn hex_to_spell(hex: &str) -> Result<Spell, Error> {
+    Ok(Spell::decode(&hex_to_bytes(hex)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_non_ascii_digit_whole() {
+        assert_eq!(
+            hex_to_bytes("0aé"),
+            Err(HexError::InvalidDigit {
+                offset: 2,
+                digit: 'é'
+            })
+        );
+    }
+
+    #[test]
+    fn spell_round_trips_through_hex_in_either_case() {
+        let spell =
+            crate::testing::spell("hex", vec![crate::testing::piece("psi:connector", 1, 0)]);
+        let hex = spell_to_hex(&spell).unwrap();
+        assert_eq!(hex, bytes_to_hex(&spell.bin().unwrap()));
+        assert!(!hex.bytes().any(|b| b.is_ascii_lowercase()));
+        assert_eq!(hex_to_spell(&hex).unwrap(), spell);
+        assert_eq!(
+            hex_to_bytes(&hex.to_lowercase()).unwrap(),
+            spell.bin().unwrap()
+        );
+    }
+
+    #[test]
+    fn odd_length_and_bad_digits_are_reported() {
+        assert_eq!(hex_to_bytes("abc"), Err(HexError::OddLength(3)));
+        assert_eq!(
+            hex_to_bytes("0g"),
+            Err(HexError::InvalidDigit {
+                offset: 1,
+                digit: 'g'
+            })
+        );
+    }
+}
@@ -1,20 +1,92 @@
-use tsify::{declare, Tsify};
-use wasm_bindgen::prelude::*;
+// Without the `std` feature the crate only needs `alloc`. Tests keep `std`,
+// which their harness links anyway.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[macro_use]
+extern crate alloc;
 
-use std::{
-    collections::HashMap,
-    io::{BufRead, Cursor, Read},
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
 };
+#[cfg(feature = "snbt")]
+use std::io::Cursor;
+
+#[cfg(feature = "std")]
+use tsify::{declare, Tsify};
 
-use quartz_nbt::{io::Flavor, serde::deserialize_from_buffer};
+#[cfg(feature = "snbt")]
+pub use quartz_nbt::io::Flavor;
+#[cfg(feature = "snbt")]
+use quartz_nbt::{serde::deserialize_from_buffer, NbtCompound};
 use serde::{Deserialize, Serialize};
-use wasm_bindgen::JsError;
 
-type JsResult<T> = Result<T, JsError>;
+mod batch;
+mod borrowed;
+#[cfg(feature = "compression")]
+mod compression;
+mod decoder;
+mod diff;
+mod fingerprint;
+mod format;
+mod grid;
+mod hex;
+mod merge;
+#[cfg(feature = "std")]
+mod namespaces;
+mod normalize;
+mod reader;
+mod render;
+mod size;
+#[cfg(feature = "std")]
+mod template;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "url-safe")]
+mod url_safe;
+mod validate;
+#[cfg(feature = "std")]
+pub mod wasm;
+
+pub use batch::{decode_many, decode_many_lenient, encode_many};
+pub use borrowed::{
+    DecodeStats, ModRef, ParamOrigin, PieceParamOrigins, PieceRef, SpellDataRef, SpellRef,
+};
+#[cfg(feature = "compression")]
+pub use compression::{
+    compress, compress_checked, compress_with_dict, decompress, decompress_with_dict, Codec,
+    NullCodec, ZstdDictCodec,
+};
+pub use decoder::{DecodeEvent, SpellDecoder};
+pub use diff::{ParamChange, PieceChange, PieceMove, SpellDiff};
+pub use fingerprint::dedup_spells;
+use format::{Layout, BUILTIN_PARAMS, HEADER_TAG, MAX_PARAMS};
+pub use grid::Grid;
+pub use hex::{bytes_to_hex, hex_to_bytes, hex_to_spell, spell_to_hex, HexError};
+pub use merge::{merge_params, MergePolicy, ParamConflict};
+#[cfg(feature = "std")]
+pub use namespaces::ModUsage;
+pub use normalize::{NormalizeOptions, NormalizeReport};
+pub use size::SizeBreakdown;
+#[cfg(feature = "std")]
+pub use template::TemplateError;
+#[cfg(feature = "url-safe")]
+pub use url_safe::{
+    bytes_to_checked_base64, bytes_to_compressed_base64, bytes_to_url_safe,
+    bytes_to_url_safe_with_codec, bytes_to_url_safe_with_dict, compressed_base64_to_bytes,
+    peek_url_safe_name, raw_base64_to_spell, recompress_url_safe, recompress_url_safe_with_dict,
+    shortest_url_safe, spell_to_checked_url_safe, spell_to_raw_base64, spell_to_url_safe,
+    spells_to_url_safe, url_safe_to_bytes, url_safe_to_bytes_with_codec,
+    url_safe_to_bytes_with_dict, url_safe_to_spell, url_safe_to_spells, Base64Error, Base64Variant,
+    CompressionStats, Spells,
+};
+pub use validate::{ValidationIssue, ValidationIssues};
 
-#[derive(Tsify, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Tsify), tsify(into_wasm_abi, from_wasm_abi))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
-#[tsify(into_wasm_abi, from_wasm_abi)]
 pub struct Spell {
     #[serde(rename = "modsRequired")]
     #[serde(default)]
@@ -25,9 +97,10 @@ pub struct Spell {
     pub name: String,
 }
 
-#[derive(Tsify, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Tsify), tsify(into_wasm_abi, from_wasm_abi))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
-#[tsify(into_wasm_abi, from_wasm_abi)]
 pub struct Mod {
     #[serde(rename = "modName")]
     pub name: String,
@@ -35,79 +108,792 @@ pub struct Mod {
     pub version: String,
 }
 
-#[derive(Tsify, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Tsify), tsify(into_wasm_abi, from_wasm_abi))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
-#[tsify(into_wasm_abi, from_wasm_abi)]
 pub struct Piece {
     pub data: SpellData,
     pub x: u8,
     pub y: u8,
 }
 
-const BUILTIN_PARAMS: [&str; 43] = [
-    "_target",
-    "_number",
-    "_number1",
-    "_number2",
-    "_number3",
-    "_number4",
-    "_vector1",
-    "_vector2",
-    "_vector3",
-    "_vector4",
-    "_position",
-    "_min",
-    "_max",
-    "_power",
-    "_x",
-    "_y",
-    "_z",
-    "_radius",
-    "_distance",
-    "_time",
-    "_base",
-    "_ray",
-    "_vector",
-    "_axis",
-    "_angle",
-    "_pitch",
-    "_instrument",
-    "_volume",
-    "_list1",
-    "_list2",
-    "_list",
-    "_direction",
-    "_from1",
-    "_from2",
-    "_to1",
-    "_to2",
-    "_root",
-    "_toggle",
-    "_mask",
-    "_channel",
-    "_slot",
-    "_ray_end",
-    "_ray_start",
-];
-
-#[declare]
-pub type SpellParams = HashMap<String, u8>;
-
-#[derive(Tsify, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", declare)]
+/// Ordered by name, so params encode in a stable order and a decoded spell
+/// re-encodes to the bytes it came from.
+pub type SpellParams = BTreeMap<String, u8>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Tsify), tsify(into_wasm_abi, from_wasm_abi))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
-#[tsify(into_wasm_abi, from_wasm_abi)]
 pub struct SpellData {
     pub key: String,
     pub params: Option<SpellParams>,
     #[serde(rename = "constantValue")]
     pub constant: Option<String>,
     pub comment: Option<String>,
+    /// Whatever editors want to keep alongside a piece. It never reaches the
+    /// binary form, so a decoded spell always has `None` here. JSON keeps any
+    /// value; NBT has no null or boolean and only lists of one element type,
+    /// so `to_snbt` and `spell_to_nbt_bytes` refuse values that use those
+    /// rather than write something that reads back different.
+    #[cfg(feature = "json")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tsify(type = "unknown")]
+    pub extra: Option<serde_json::Value>,
+}
+
+/// The fields stay public, but these are the stable way in: the types behind
+/// them, like the params map, may change without the accessors changing.
+impl Spell {
+    /// The name shown in the programmer.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.name(), "blink");
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The mods the spell declares it needs, in the order they were listed.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// let mods = spell.mods();
+    /// assert_eq!(mods.len(), 1);
+    /// assert_eq!(mods[0].name(), "psi");
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn mods(&self) -> &[Mod] {
+        &self.mods
+    }
+
+    /// Every placed piece, in the order they were stored.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.pieces().len(), 2);
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn pieces(&self) -> &[Piece] {
+        &self.pieces
+    }
+}
+
+impl Mod {
+    /// The mod id, also the namespace of its piece keys.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.mods()[0].name(), "psi");
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The version string the spell was made with, never parsed.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.mods()[0].version(), "r1.0");
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+}
+
+impl Piece {
+    /// What the piece is, apart from where it sits.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.pieces()[0].data().key(), "psi:constant_number");
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn data(&self) -> &SpellData {
+        &self.data
+    }
+
+    /// The piece's `(x, y)` cell in the grid.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.pieces()[0].position(), (1, 2));
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn position(&self) -> (u8, u8) {
+        (self.x, self.y)
+    }
+}
+
+/// Keys are namespaced like Minecraft ids. A bare key is a `psi` key: the binary
+/// form drops the `psi:` prefix and decoding restores it, so a bare key comes
+/// back prefixed. Keys from any other mod keep their namespace through both.
+/// A `psi:` key with a second namespace after it, like `psi:phi:x`, cannot be
+/// told apart from the `phi:x` it is stored as and is refused by `bin`.
+impl SpellData {
+    /// The full piece key. Decoding always gives one with a namespace.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.pieces()[1].data().key(), "psi:operator_sum");
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Each param's name and the side it reads from, if the piece takes any.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// let params = spell.pieces()[1].data().params().unwrap();
+    /// assert_eq!(params["_number"], 3);
+    /// assert_eq!(spell.pieces()[0].data().params(), None);
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn params(&self) -> Option<&SpellParams> {
+        self.params.as_ref()
+    }
+
+    /// The typed-in value of a constant piece.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.pieces()[0].data().constant(), Some("8"));
+    /// assert_eq!(spell.pieces()[1].data().constant(), None);
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn constant(&self) -> Option<&str> {
+        self.constant.as_deref()
+    }
+
+    /// The note attached to the piece, if any.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.pieces()[0].data().comment(), Some("count"));
+    /// assert_eq!(spell.pieces()[1].data().comment(), None);
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// The part of the key before `:`, or `psi` for a bare key.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.pieces()[0].data().namespace(), "psi");
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn namespace(&self) -> &str {
+        self.key
+            .split_once(':')
+            .map_or("psi", |(namespace, _)| namespace)
+    }
+
+    /// The key without its namespace.
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::Spell;
+    /// # const SPELL: &[u8] = b"blink\0psi,r1.0;]\x12constant_number\0count\0\xff8\0\x22operator_sum\0\0\x01\x01\x03";
+    /// let spell = Spell::decode(SPELL)?;
+    /// assert_eq!(spell.pieces()[0].data().path(), "constant_number");
+    /// # Ok::<(), psi_spell_encode_wasm::DecodeError>(())
+    /// ```
+    pub fn path(&self) -> &str {
+        self.key.split_once(':').map_or(&self.key, |(_, path)| path)
+    }
+}
+
+/// The values Psi stores for a param's side, in ordinal order: 0 to 4 are the
+/// only legal side bytes, anything above is a corrupt or hand-edited spell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Off,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn offset(self) -> Option<(i8, i8)> {
+        match self {
+            Side::Off => None,
+            Side::Top => Some((0, -1)),
+            Side::Bottom => Some((0, 1)),
+            Side::Left => Some((-1, 0)),
+            Side::Right => Some((1, 0)),
+        }
+    }
+}
+
+impl TryFrom<u8> for Side {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Side::Off),
+            1 => Ok(Side::Top),
+            2 => Ok(Side::Bottom),
+            3 => Ok(Side::Left),
+            4 => Ok(Side::Right),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    ModName,
+    ModVersion,
+    Key,
+    Comment,
+    Constant,
+    ParamName,
+}
+
+impl core::fmt::Display for Field {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Field::Name => "spell name",
+            Field::ModName => "mod name",
+            Field::ModVersion => "mod version",
+            Field::Key => "piece key",
+            Field::Comment => "piece comment",
+            Field::Constant => "piece constant",
+            Field::ParamName => "param name",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    TooManyParams {
+        x: u8,
+        y: u8,
+        count: usize,
+    },
+    NameTooLong {
+        len: usize,
+        max: usize,
+    },
+    ReservedByte {
+        field: Field,
+        position: Option<(u8, u8)>,
+        byte: u8,
+    },
+    /// Only one of the two can be written.
+    ConstantWithParams {
+        x: u8,
+        y: u8,
+    },
+    /// A `psi:` key whose path has a namespace of its own, like `psi:phi:x`.
+    /// The binary form drops the `psi:`, so it would decode as `phi:x`.
+    AmbiguousKey {
+        x: u8,
+        y: u8,
+    },
+}
+
+impl core::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EncodeError::TooManyParams { x, y, count } => write!(
+                f,
+                "piece at ({x}, {y}) has {count} params, at most {MAX_PARAMS} can be encoded"
+            ),
+            EncodeError::NameTooLong { len, max } => write!(
+                f,
+                "spell name is {len} characters long, at most {max} are allowed"
+            ),
+            EncodeError::ReservedByte {
+                field,
+                position: Some((x, y)),
+                byte,
+            } => write!(
+                f,
+                "{field} of piece at ({x}, {y}) contains reserved byte {byte:#04x}"
+            ),
+            EncodeError::ReservedByte {
+                field,
+                position: None,
+                byte,
+            } => write!(f, "{field} contains reserved byte {byte:#04x}"),
+            EncodeError::ConstantWithParams { x, y } => write!(
+                f,
+                "piece at ({x}, {y}) has both params and a constant, only one can be encoded"
+            ),
+            EncodeError::AmbiguousKey { x, y } => write!(
+                f,
+                "key of piece at ({x}, {y}) has a second namespace after `psi:`, \
+                 which would decode as the key's own"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for EncodeError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEof,
+    /// `offset` is that of the first bad byte in the whole input.
+    Utf8 {
+        field: Field,
+        offset: usize,
+    },
+    ChecksumMismatch {
+        expected: u16,
+        actual: u16,
+    },
+    TrailingData {
+        offset: usize,
+    },
+    TooManyPieces {
+        max: usize,
+    },
+    UnknownParam(u8),
+    UnknownParamRef(u8),
+    MissingModsTerminator,
+    /// The header flag bits this version does not know, from a newer encoder.
+    UnknownFlags(u8),
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof => f.write_str("unexpected end of spell data"),
+            DecodeError::Utf8 { field, offset } => {
+                write!(f, "invalid UTF-8 in {field} at offset {offset}")
+            }
+            DecodeError::UnknownFlags(bits) => write!(
+                f,
+                "header has unknown flag bits {bits:#04x}, the spell needs a newer decoder"
+            ),
+            DecodeError::MissingModsTerminator => {
+                f.write_str("mods list is missing its closing `]`, the data may be truncated")
+            }
+            DecodeError::UnknownParamRef(index) => {
+                write!(f, "param table has no entry {index}")
+            }
+            DecodeError::UnknownParam(index) => {
+                write!(f, "param index {index} is not a known builtin param")
+            }
+            DecodeError::TooManyPieces { max } => {
+                write!(f, "spell data holds more than {max} pieces")
+            }
+            DecodeError::TrailingData { offset } => write!(
+                f,
+                "trailing data at offset {offset} does not form a complete piece"
+            ),
+            DecodeError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch (expected {expected:#06x}, got {actual:#06x}); the spell string is likely truncated or corrupt"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+#[derive(Debug)]
+pub enum Error {
+    Encode(EncodeError),
+    Decode(DecodeError),
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    Hex(HexError),
+    #[cfg(feature = "url-safe")]
+    Base64(Base64Error),
+    /// `line` and `column` count from 1, in characters.
+    #[cfg(feature = "snbt")]
+    Snbt {
+        line: usize,
+        column: usize,
+        source: quartz_nbt::snbt::SnbtError,
+    },
+    #[cfg(feature = "snbt")]
+    MissingField(&'static str),
+    #[cfg(feature = "snbt")]
+    Nbt(quartz_nbt::io::NbtIoError),
+    #[cfg(feature = "snbt")]
+    NoSpellInItem,
+    #[cfg(all(feature = "snbt", feature = "json"))]
+    ExtraNotNbt {
+        x: u8,
+        y: u8,
+    },
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Encode(e) => e.fmt(f),
+            Error::Decode(e) => e.fmt(f),
+            #[cfg(feature = "std")]
+            Error::Io(e) => e.fmt(f),
+            Error::Hex(e) => e.fmt(f),
+            #[cfg(feature = "url-safe")]
+            Error::Base64(e) => e.fmt(f),
+            #[cfg(feature = "snbt")]
+            Error::Snbt {
+                line,
+                column,
+                source,
+            } => write!(f, "invalid SNBT at line {line}, column {column}: {source}"),
+            #[cfg(feature = "snbt")]
+            Error::MissingField(field) => write!(f, "spell SNBT is missing `{field}`"),
+            #[cfg(feature = "snbt")]
+            Error::Nbt(e) => e.fmt(f),
+            #[cfg(feature = "snbt")]
+            Error::NoSpellInItem => {
+                f.write_str("item NBT has no spell compound under `spell`, `spellData`, or `tag`")
+            }
+            #[cfg(all(feature = "snbt", feature = "json"))]
+            Error::ExtraNotNbt { x, y } => write!(
+                f,
+                "extra data of piece at ({x}, {y}) holds a null, a boolean, or a mixed list, \
+                 which NBT cannot store"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<EncodeError> for Error {
+    fn from(value: EncodeError) -> Self {
+        Error::Encode(value)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(value: DecodeError) -> Self {
+        Error::Decode(value)
+    }
 }
 
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Error::Io(value)
+    }
+}
+
+impl From<HexError> for Error {
+    fn from(value: HexError) -> Self {
+        Error::Hex(value)
+    }
+}
+
+#[cfg(feature = "url-safe")]
+impl From<Base64Error> for Error {
+    fn from(value: Base64Error) -> Self {
+        Error::Base64(value)
+    }
+}
+
+#[cfg(feature = "snbt")]
+impl From<quartz_nbt::io::NbtIoError> for Error {
+    fn from(value: quartz_nbt::io::NbtIoError) -> Self {
+        Error::Nbt(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommentMode {
+    Omit,
+    #[default]
+    Inline,
+    Separate,
+}
+
+/// Psi's programmer GUI caps spell names at 20 characters.
+pub const DEFAULT_MAX_NAME_LEN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BinOptions {
+    pub comments: CommentMode,
+    /// Counted in UTF-16 code units, the unit Java's `String::length` and so Psi's
+    /// name field use. Multi-byte UTF-8 characters count once unless they are
+    /// outside the Basic Multilingual Plane.
+    pub max_name_len: usize,
+}
+
+impl Default for BinOptions {
+    fn default() -> Self {
+        Self {
+            comments: CommentMode::default(),
+            max_name_len: DEFAULT_MAX_NAME_LEN,
+        }
+    }
+}
+
+/// Far more than a 9x9 programmer grid can hold, even with extended positions in
+/// play, while still bounding what an untrusted string can make us allocate.
+pub const DEFAULT_MAX_PIECES: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOptions {
+    pub max_pieces: usize,
+    /// Decodes builtin param indices this version does not know, say from a
+    /// newer Psi, to `_builtin_<index>` instead of failing. Such a param
+    /// re-encodes under that name, not its original index.
+    pub lenient_params: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            max_pieces: DEFAULT_MAX_PIECES,
+            lenient_params: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverlayError {
+    Collision { x: u8, y: u8 },
+    OutOfGrid { x: usize, y: usize },
+}
+
+impl core::fmt::Display for OverlayError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            OverlayError::Collision { x, y } => {
+                write!(
+                    f,
+                    "overlaid piece collides with existing piece at ({x}, {y})"
+                )
+            }
+            OverlayError::OutOfGrid { x, y } => write!(
+                f,
+                "overlaid piece would land at ({x}, {y}), outside the grid (0-{})",
+                u8::MAX
+            ),
+        }
+    }
+}
+
+impl core::error::Error for OverlayError {}
+
 impl Spell {
+    pub fn into_parts(self) -> (String, Vec<Mod>, Vec<Piece>) {
+        (self.name, self.mods, self.pieces)
+    }
+
+    pub fn param_counts(&self) -> Vec<((u8, u8), usize)> {
+        self.pieces
+            .iter()
+            .map(|piece| {
+                let count = piece.data.params.as_ref().map_or(0, |params| params.len());
+                ((piece.x, piece.y), count)
+            })
+            .collect()
+    }
+
+    pub fn constants(&self) -> Vec<((u8, u8), String)> {
+        self.pieces
+            .iter()
+            .filter_map(|piece| match &piece.data.constant {
+                Some(constant) if !constant.is_empty() => {
+                    Some(((piece.x, piece.y), constant.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn distinct_keys(&self) -> BTreeSet<String> {
+        self.pieces.iter().map(|p| p.data.key.clone()).collect()
+    }
+
+    /// Param sides are relative to their piece, so shifting a whole fragment
+    /// keeps its internal connections intact without rewriting them.
+    pub fn overlay(&mut self, other: &Spell, dx: u8, dy: u8) -> Result<(), OverlayError> {
+        let mut placed = Vec::with_capacity(other.pieces.len());
+        for piece in &other.pieces {
+            let x = piece.x as usize + dx as usize;
+            let y = piece.y as usize + dy as usize;
+            if x > u8::MAX as usize || y > u8::MAX as usize {
+                return Err(OverlayError::OutOfGrid { x, y });
+            }
+            let (x, y) = (x as u8, y as u8);
+            if self.pieces.iter().any(|p| p.x == x && p.y == y) {
+                return Err(OverlayError::Collision { x, y });
+            }
+            placed.push(Piece {
+                data: piece.data.clone(),
+                x,
+                y,
+            });
+        }
+
+        self.pieces.extend(placed);
+        Ok(())
+    }
+
+    /// Moves the whole spell so its bounding box starts at (0, 0). Gaps between
+    /// pieces stay, since params reach their inputs through adjacent sides and
+    /// closing a gap would rewire them; a plain shift leaves every side valid.
+    pub fn compact_positions(&mut self) {
+        let min_x = self.pieces.iter().map(|p| p.x).min().unwrap_or(0);
+        let min_y = self.pieces.iter().map(|p| p.y).min().unwrap_or(0);
+        for piece in &mut self.pieces {
+            piece.x -= min_x;
+            piece.y -= min_y;
+        }
+    }
+
+    pub fn encode_errors(&self) -> Vec<EncodeError> {
+        self.encode_errors_with(BinOptions::default())
+    }
+
+    /// Fails exactly when `bin` would, with the same error, but builds nothing.
+    /// Cheap enough to run on every edit.
+    pub fn check_encodable(&self) -> Result<(), EncodeError> {
+        match self.encode_errors().into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn encode_errors_with(&self, options: BinOptions) -> Vec<EncodeError> {
+        fn check(
+            errors: &mut Vec<EncodeError>,
+            value: &str,
+            field: Field,
+            position: Option<(u8, u8)>,
+            reserved: &[u8],
+        ) {
+            if let Some(&byte) = value.as_bytes().iter().find(|b| reserved.contains(b)) {
+                errors.push(EncodeError::ReservedByte {
+                    field,
+                    position,
+                    byte,
+                });
+            }
+        }
+
+        let mut errors = Vec::new();
+        let name_len = self.name.encode_utf16().count();
+        if name_len > options.max_name_len {
+            errors.push(EncodeError::NameTooLong {
+                len: name_len,
+                max: options.max_name_len,
+            });
+        }
+        check(&mut errors, &self.name, Field::Name, None, &[0]);
+        if self.name.as_bytes().first() == Some(&HEADER_TAG) {
+            errors.push(EncodeError::ReservedByte {
+                field: Field::Name,
+                position: None,
+                byte: HEADER_TAG,
+            });
+        }
+        for m in &self.mods {
+            check(&mut errors, &m.name, Field::ModName, None, b",;]");
+            check(&mut errors, &m.version, Field::ModVersion, None, b",;]");
+        }
+
+        for piece in &self.pieces {
+            let (x, y) = (piece.x, piece.y);
+            let data = &piece.data;
+            check(&mut errors, &data.key, Field::Key, Some((x, y)), &[0]);
+            if data
+                .key
+                .strip_prefix("psi:")
+                .is_some_and(|path| path.contains(':'))
+            {
+                errors.push(EncodeError::AmbiguousKey { x, y });
+            }
+            if let Some(comment) = &data.comment {
+                check(&mut errors, comment, Field::Comment, Some((x, y)), &[0]);
+            }
+
+            if let (Some(params), Some(_)) = (&data.params, &data.constant) {
+                if !params.is_empty() {
+                    errors.push(EncodeError::ConstantWithParams { x, y });
+                }
+            }
+
+            if let Some(params) = format::params_to_encode(data) {
+                if params.len() > MAX_PARAMS {
+                    errors.push(EncodeError::TooManyParams {
+                        x,
+                        y,
+                        count: params.len(),
+                    });
+                }
+                for key in params.keys() {
+                    if !BUILTIN_PARAMS.contains(&key.as_str()) {
+                        check(&mut errors, key, Field::ParamName, Some((x, y)), &[0]);
+                    }
+                }
+            } else if let Some(constant) = &data.constant {
+                check(&mut errors, constant, Field::Constant, Some((x, y)), &[0]);
+            }
+        }
+
+        errors
+    }
+
     #[inline]
-    pub fn bin(&self) -> Vec<u8> {
-        let mut out: Vec<u8> = Vec::new();
+    pub fn bin(&self) -> Result<Vec<u8>, EncodeError> {
+        self.bin_with_options(BinOptions::default())
+            .map(|(out, _)| out)
+    }
+
+    /// With `CommentMode::Separate` the second element holds every piece's comment
+    /// in piece order, ready for `attach_comments` after decoding.
+    pub fn bin_with_options(
+        &self,
+        options: BinOptions,
+    ) -> Result<(Vec<u8>, Vec<Option<String>>), EncodeError> {
+        if let Some(err) = self.encode_errors_with(options).into_iter().next() {
+            return Err(err);
+        }
+
+        let layout = Layout::of(&self.pieces);
+        let mut out: Vec<u8> = Vec::with_capacity(self.measure(&layout).total());
+        let mut comments = Vec::new();
+        if layout.flags() != 0 {
+            out.push(HEADER_TAG);
+            out.push(layout.flags());
+        }
         {
             let name = self.name.as_bytes();
             out.extend_from_slice(name);
@@ -129,253 +915,977 @@ impl Spell {
             out.push(b']');
         }
 
-        for piece in &self.pieces {
-            let data = &piece.data;
-            let key = data.key.as_bytes();
-            let key = if &key[0..4] == b"psi:" {
-                &key[4..]
-            } else {
-                key
-            };
-            let params = &data.params;
-            let constant = &data.constant;
-            let comment = &data.comment;
-            out.push(piece.x << 4 | (piece.y & 0b1111));
-            out.extend_from_slice(key);
-            out.push(0);
-            if let Some(comment) = comment {
-                out.extend_from_slice(comment.as_bytes());
+        if let Some(table) = &layout.param_table {
+            out.push(table.len() as u8);
+            for name in table {
+                out.extend_from_slice(name.as_bytes());
+                out.push(0);
             }
-            out.push(0);
+        }
 
-            if let Some(params) = params {
-                out.push(params.len() as u8);
-                for (key, side) in params {
-                    if let Some(pos) = BUILTIN_PARAMS.iter().position(|e| **e == *key) {
-                        out.push(pos as u8);
-                    } else {
-                        out.push(255);
-                        out.extend_from_slice(key.as_bytes());
-                        out.push(0);
-                    }
-                    out.push(*side);
+        for piece in &self.pieces {
+            let comment = match options.comments {
+                CommentMode::Inline => piece.data.comment.as_deref(),
+                CommentMode::Omit => None,
+                CommentMode::Separate => {
+                    comments.push(piece.data.comment.clone());
+                    None
                 }
-            } else if let Some(constant) = constant {
-                out.push(255);
-                out.extend_from_slice(constant.as_bytes());
-                out.push(0);
-            } else {
-                out.push(254);
-            }
+            };
+            format::encode_piece(&mut out, piece, &layout, comment);
         }
 
-        out
+        Ok((out, comments))
     }
 
-    #[inline]
-    pub fn decode(data: &[u8]) -> JsResult<Self> {
-        #[inline]
-        fn read_until<T>(cursor: &mut Cursor<T>, byte: u8) -> JsResult<Vec<u8>>
-        where
-            T: std::convert::AsRef<[u8]>,
-        {
-            let mut out = Vec::new();
-            cursor.read_until(byte, &mut out)?;
-            out.pop();
-            Ok(out)
+    /// Each comment costs its UTF-8 length in the binary form, and compresses
+    /// far worse than keys do, so stripping them is usually the biggest saving
+    /// available for a shared string. Only the notes are lost; the spell casts
+    /// the same. `BinOptions { comments: CommentMode::Omit, .. }` does the same
+    /// without touching the spell.
+    pub fn strip_comments(&mut self) {
+        for piece in &mut self.pieces {
+            piece.data.comment = None;
         }
+    }
 
-        #[inline]
-        fn read_until_nul<T>(cursor: &mut Cursor<T>) -> JsResult<Vec<u8>>
-        where
-            T: std::convert::AsRef<[u8]>,
-        {
-            read_until(cursor, 0)
-        }
+    pub fn without_comments(&self) -> Spell {
+        let mut spell = self.clone();
+        spell.strip_comments();
+        spell
+    }
 
-        #[inline]
-        fn next<T>(cursor: &mut Cursor<T>) -> JsResult<u8>
-        where
-            T: std::convert::AsRef<[u8]>,
-        {
-            let mut a = [0];
-            cursor.read_exact(&mut a)?;
-            Ok(a[0])
-        }
+    /// Keeps a mod only while some piece key lives in its namespace. Bare keys
+    /// count as `psi`, so a listed `psi` entry survives as long as the spell
+    /// has any vanilla pieces, but none is ever added for them.
+    pub fn prune_unused_mods(&mut self) {
+        let pieces = &self.pieces;
+        self.mods
+            .retain(|m| pieces.iter().any(|p| p.data.namespace() == m.name));
+    }
 
-        #[inline]
-        fn btos(b: Vec<u8>) -> JsResult<String> {
-            Ok(String::from_utf8(b)?)
+    pub fn attach_comments(&mut self, comments: Vec<Option<String>>) {
+        for (piece, comment) in self.pieces.iter_mut().zip(comments) {
+            piece.data.comment = comment;
         }
+    }
 
-        let mut cursor = Cursor::new(data);
-        let name = btos(read_until_nul(&mut cursor)?)?;
-        let mut mods = Vec::new();
-        let mut pieces = Vec::new();
+    #[inline]
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        Spell::decode_with_options(data, DecodeOptions::default())
+    }
 
-        {
-            let m = read_until(&mut cursor, b']')?;
-            for m in m.split(|b| *b == b';') {
-                let mut name = Vec::new();
-                let mut version = Vec::new();
-                let mut name_done = false;
-                for b in m {
-                    let b = *b;
-                    if b == b',' || b == b';' {
-                        name_done = true;
-                        continue;
-                    }
-                    if !name_done {
-                        name.push(b);
-                    } else {
-                        version.push(b);
-                    }
-                }
-                mods.push(Mod {
-                    name: btos(name)?,
-                    version: btos(version)?,
-                })
+    pub fn decode_with_options(data: &[u8], options: DecodeOptions) -> Result<Self, DecodeError> {
+        Spell::decode_borrowed_with_options(data, options).map(SpellRef::into_owned)
+    }
+
+    /// Stops at the first piece the rest of the data cannot complete, and
+    /// returns how many bytes made up the spell. For blobs that carry something
+    /// of their own after the spell.
+    pub fn decode_with_remainder(data: &[u8]) -> Result<(Self, usize), DecodeError> {
+        let (spell, _, consumed) = Spell::decode_prefix(data, DecodeOptions::default())?;
+        Ok((spell.into_owned(), consumed))
+    }
+}
+
+impl TryFrom<&Spell> for Vec<u8> {
+    type Error = EncodeError;
+
+    #[inline]
+    fn try_from(value: &Spell) -> Result<Self, Self::Error> {
+        value.bin()
+    }
+}
+
+#[cfg(feature = "snbt")]
+fn parse_snbt(snbt: &str) -> Result<NbtCompound, Error> {
+    quartz_nbt::snbt::parse(snbt).map_err(|source| {
+        let before = snbt.char_indices().take_while(|(i, _)| *i < source.index);
+        let (mut line, mut column) = (1, 1);
+        for (_, c) in before {
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
             }
         }
+        Error::Snbt {
+            line,
+            column,
+            source,
+        }
+    })
+}
 
-        while cursor.fill_buf().map(|b| !b.is_empty())? {
-            let xy = next(&mut cursor)?;
-            let x = xy >> 4;
-            let y = xy & 0b1111;
-            let mut key = read_until_nul(&mut cursor)?;
-            if !key.contains(&b':') {
-                key.reserve(4);
-                unsafe {
-                    std::ptr::copy(key.as_ptr(), key.as_mut_ptr().add(4), key.len());
-                    key.set_len(key.len() + 4);
-                }
-                key[0] = b'p';
-                key[1] = b's';
-                key[2] = b'i';
-                key[3] = b':';
+#[cfg(feature = "snbt")]
+impl Spell {
+    pub fn from_snbt(snbt: &str) -> Result<Self, Error> {
+        Spell::from_compound(&parse_snbt(snbt)?)
+    }
+
+    fn from_compound(compound: &NbtCompound) -> Result<Self, Error> {
+        // Serde would only say a field is missing somewhere; these are the
+        // ones people leave out when pasting a spell by hand.
+        for field in ["spellName", "spellList"] {
+            if !compound.contains_key(field) {
+                return Err(Error::MissingField(field));
             }
-            let key = btos(key)?;
+        }
 
-            let comment = btos(read_until_nul(&mut cursor)?)?;
-            let comment = if comment.is_empty() {
-                None
-            } else {
-                Some(comment)
-            };
+        let mut bytes = Vec::new();
+        quartz_nbt::io::write_nbt(&mut bytes, None, compound, Flavor::Uncompressed)?;
 
-            let mut params = HashMap::new();
-            let mut constant = None;
-
-            let ty = next(&mut cursor)?;
-            if ty == 255 {
-                constant = Some(btos(read_until_nul(&mut cursor)?)?);
-            } else if ty != 254 {
-                let len = ty;
-                for _ in 0..len {
-                    let type_or_pos = next(&mut cursor)?;
-                    let param_key = if type_or_pos == 255 {
-                        btos(read_until_nul(&mut cursor)?)?
-                    } else {
-                        BUILTIN_PARAMS[type_or_pos as usize].to_string()
-                    };
-
-                    let side = next(&mut cursor)?;
-                    params.insert(param_key, side);
-                }
-            }
+        let spell = deserialize_from_buffer::<Spell>(&bytes)?.0;
 
-            let params = if params.is_empty() {
-                None
-            } else {
-                Some(params)
-            };
+        Ok(spell)
+    }
 
-            let data = SpellData {
-                key,
-                params,
-                constant,
-                comment,
-            };
+    pub fn to_snbt(&self) -> Result<String, Error> {
+        self.check_extra_fits_nbt()?;
+        let ser = quartz_nbt::serde::serialize(self, None, Flavor::Uncompressed)?;
+        let nbt = quartz_nbt::io::read_nbt(&mut Cursor::new(ser), Flavor::Uncompressed)?;
+        Ok(nbt.0.to_snbt())
+    }
 
-            let piece = Piece { data, x, y };
-            pieces.push(piece);
+    fn check_extra_fits_nbt(&self) -> Result<(), Error> {
+        #[cfg(feature = "json")]
+        for piece in &self.pieces {
+            if piece
+                .data
+                .extra
+                .as_ref()
+                .is_some_and(|extra| !fits_nbt(extra))
+            {
+                return Err(Error::ExtraNotNbt {
+                    x: piece.x,
+                    y: piece.y,
+                });
+            }
         }
-
-        Ok(Self { name, mods, pieces })
+        Ok(())
     }
 }
 
-impl From<&Spell> for Vec<u8> {
-    #[inline]
-    fn from(value: &Spell) -> Self {
-        value.bin()
+// Whether a JSON value reads back the same after a trip through NBT.
+#[cfg(all(feature = "snbt", feature = "json"))]
+fn fits_nbt(value: &serde_json::Value) -> bool {
+    use serde_json::Value;
+
+    // The NBT tag each element would be written as; a list needs one for all.
+    fn tag(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(n) if n.is_f64() => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+
+    match value {
+        Value::Null | Value::Bool(_) => false,
+        Value::Number(_) | Value::String(_) => true,
+        Value::Array(items) => {
+            items.iter().all(fits_nbt) && items.windows(2).all(|w| tag(&w[0]) == tag(&w[1]))
+        }
+        Value::Object(fields) => fields.values().all(fits_nbt),
     }
 }
 
-#[wasm_bindgen(js_name = "snbtToSpell")]
-pub fn snbt_to_spell(snbt: &str) -> JsResult<Spell> {
-    let snbt = quartz_nbt::snbt::parse(snbt)?;
+/// Full item NBT, as exported from a world save, rather than just the spell
+/// compound. Spell drives keep the spell in `tag.spell`; the compound can
+/// also be given bare or under `spellData`.
+#[cfg(feature = "snbt")]
+pub fn spell_from_item_nbt(snbt: &str) -> Result<Spell, Error> {
+    fn find_spell(item: &NbtCompound) -> Option<&NbtCompound> {
+        if item.contains_key("spellList") {
+            return Some(item);
+        }
+        ["spell", "spellData"]
+            .into_iter()
+            .find_map(|key| item.get::<_, &NbtCompound>(key).ok())
+            .or_else(|| find_spell(item.get::<_, &NbtCompound>("tag").ok()?))
+    }
 
-    let mut bytes = Vec::new();
-    quartz_nbt::io::write_nbt(&mut bytes, None, &snbt, Flavor::Uncompressed)?;
+    let item = parse_snbt(snbt)?;
+    Spell::from_compound(find_spell(&item).ok_or(Error::NoSpellInItem)?)
+}
 
-    let spell = deserialize_from_buffer::<Spell>(&bytes)?.0;
+/// Binary NBT as Minecraft writes it to disk, `.dat` files being
+/// `Flavor::GzCompressed`.
+#[cfg(feature = "snbt")]
+pub fn spell_to_nbt_bytes(spell: &Spell, flavor: Flavor) -> Result<Vec<u8>, Error> {
+    spell.check_extra_fits_nbt()?;
+    Ok(quartz_nbt::serde::serialize(spell, None, flavor)?)
+}
 
-    Ok(spell)
+#[cfg(feature = "snbt")]
+pub fn nbt_bytes_to_spell(bytes: &[u8], flavor: Flavor) -> Result<Spell, Error> {
+    Ok(quartz_nbt::serde::deserialize(bytes, flavor)?.0)
 }
 
-#[wasm_bindgen(js_name = "bytesToSpell")]
-pub fn bytes_to_spell(bytes: Vec<u8>) -> JsResult<Spell> {
-    let spell: Spell = Spell::decode(&bytes)?;
-    Ok(spell)
+/// Psi's programmer copies a spell to the clipboard as the SNBT of its compound,
+/// with no compression or wrapper, and parses the same back on paste. The game
+/// also writes the spell's `uuidMost` and `uuidLeast`, which are ignored here.
+#[cfg(feature = "snbt")]
+pub fn spell_to_clipboard(spell: &Spell) -> Result<String, Error> {
+    spell.to_snbt()
 }
 
-#[wasm_bindgen(js_name = "spellToBytes")]
-pub fn spell_to_bytes(spell: Spell) -> Result<Vec<u8>, JsError> {
-    Ok((&spell).into())
+#[cfg(feature = "snbt")]
+pub fn clipboard_to_spell(clipboard: &str) -> Result<Spell, Error> {
+    Spell::from_snbt(clipboard.trim())
 }
 
-#[wasm_bindgen(js_name = "urlSafeToSpell")]
-pub fn url_safe_to_spell(url_safe: String) -> JsResult<Spell> {
-    Spell::decode(&url_safe_to_bytes(url_safe)?)
+#[cfg(feature = "json")]
+pub fn spell_to_json(spell: &Spell) -> serde_json::Result<String> {
+    serde_json::to_string(spell)
 }
 
-#[wasm_bindgen(js_name = "spellToUrlSafe")]
-pub fn spell_to_url_safe(spell: Spell) -> JsResult<String> {
-    bytes_to_url_safe(spell_to_bytes(spell)?)
+#[cfg(feature = "json")]
+pub fn spell_from_json(json: &str) -> serde_json::Result<Spell> {
+    serde_json::from_str(json)
 }
 
-const ZSTD_DICT: &[u8] = include_bytes!("./zstd_dict");
+/// The same field names and optionality `spell_to_json` and `spell_from_json`
+/// use, as a JSON Schema document.
+#[cfg(feature = "schema")]
+pub fn spell_json_schema() -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&schemars::schema_for!(Spell))
+}
 
-#[wasm_bindgen(js_name = "bytesToUrlSafe")]
-pub fn bytes_to_url_safe(bytes: Vec<u8>) -> JsResult<String> {
-    let bytes =
-        zstd::bulk::Compressor::with_dictionary(22, ZSTD_DICT)?.compress(bytes.as_slice())?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
 
-    Ok(base64_simd::URL_SAFE.encode_to_string(bytes))
-}
+    #[test]
+    fn param_counts_follow_piece_order() {
+        let spell = spell(
+            "counts",
+            vec![
+                with_params(
+                    piece("psi:operator_sum", 1, 0),
+                    &[("_number1", 3), ("_number2", 4), ("_number3", 1)],
+                ),
+                piece("psi:connector", 0, 0),
+                with_params(piece("psi:trick_debug", 2, 0), &[]),
+            ],
+        );
+        assert_eq!(
+            spell.param_counts(),
+            vec![((1, 0), 3), ((0, 0), 0), ((2, 0), 0)]
+        );
+    }
 
-#[wasm_bindgen(js_name = "urlSafeToBytes")]
-pub fn url_safe_to_bytes(url_safe: String) -> JsResult<Vec<u8>> {
-    let mut bytes = url_safe.into_bytes();
-    let decoded = base64_simd::URL_SAFE.decode_inplace(&mut bytes)?.to_vec();
+    #[cfg(feature = "json")]
+    fn spell_with_extra(extra: Option<serde_json::Value>) -> Spell {
+        Spell {
+            mods: vec![],
+            pieces: vec![Piece {
+                data: SpellData {
+                    key: "psi:constant_number".to_string(),
+                    params: None,
+                    constant: Some("3".to_string()),
+                    comment: None,
+                    extra,
+                },
+                x: 4,
+                y: 5,
+            }],
+            name: "extra".to_string(),
+        }
+    }
 
-    let mut dest = Vec::new();
-    let mut decoder = zstd::stream::Decoder::with_dictionary(decoded.as_slice(), ZSTD_DICT)?;
-    std::io::copy(&mut decoder, &mut dest)?;
+    #[cfg(feature = "json")]
+    #[test]
+    fn absent_extra_is_left_out_of_json() {
+        let spell = spell_with_extra(None);
+        let json = spell_to_json(&spell).unwrap();
+        assert!(!json.contains("extra"));
+        assert_eq!(spell_from_json(&json).unwrap(), spell);
+    }
 
-    Ok(dest)
-}
+    #[cfg(feature = "json")]
+    #[test]
+    fn extra_survives_json() {
+        let spell = spell_with_extra(Some(serde_json::json!({
+            "pinned": true,
+            "note": null,
+            "tags": [1, "mixed"],
+        })));
+        let json = spell_to_json(&spell).unwrap();
+        assert_eq!(spell_from_json(&json).unwrap(), spell);
+    }
 
-#[wasm_bindgen(js_name = "spellToSnbt")]
-pub fn spell_to_snbt(spell: Spell) -> JsResult<String> {
-    let ser = quartz_nbt::serde::serialize(&spell, None, Flavor::Uncompressed).unwrap();
-    quartz_nbt::io::read_nbt(&mut Cursor::new(ser), Flavor::Uncompressed)
-        .map(|o| o.0.to_snbt())
-        .map_err(JsError::from)
-}
+    #[cfg(feature = "json")]
+    #[test]
+    fn extra_never_reaches_the_binary_form() {
+        let spell = spell_with_extra(Some(serde_json::json!({ "color": "red" })));
+        let decoded = Spell::decode(&spell.bin().unwrap()).unwrap();
+        assert_eq!(decoded, spell_with_extra(None));
+    }
 
-#[wasm_bindgen(start)]
-pub fn main() {
-    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    #[cfg(all(feature = "snbt", feature = "json"))]
+    #[test]
+    fn extra_nbt_can_hold_survives_snbt() {
+        let spell = spell_with_extra(Some(serde_json::json!({
+            "color": "red",
+            "layer": 3,
+            "tags": ["a", "b"],
+        })));
+        assert_eq!(Spell::from_snbt(&spell.to_snbt().unwrap()).unwrap(), spell);
+    }
+
+    #[cfg(all(feature = "snbt", feature = "json"))]
+    #[test]
+    fn absent_extra_survives_snbt() {
+        let spell = spell_with_extra(None);
+        assert_eq!(Spell::from_snbt(&spell.to_snbt().unwrap()).unwrap(), spell);
+    }
+
+    #[cfg(all(feature = "snbt", feature = "json"))]
+    #[test]
+    fn extra_nbt_cannot_hold_is_refused() {
+        for extra in [
+            serde_json::json!(null),
+            serde_json::json!({ "pinned": true }),
+            serde_json::json!({ "tags": [1, "mixed"] }),
+            serde_json::json!([[1], [1.5]]),
+        ] {
+            let spell = spell_with_extra(Some(extra));
+            assert!(matches!(
+                spell.to_snbt(),
+                Err(Error::ExtraNotNbt { x: 4, y: 5 })
+            ));
+            assert!(matches!(
+                spell_to_nbt_bytes(&spell, Flavor::Uncompressed),
+                Err(Error::ExtraNotNbt { x: 4, y: 5 })
+            ));
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trips_every_piece_shape() {
+        let mut constant = piece("psi:constant_number", 0, 1);
+        constant.data.constant = Some("2.5".to_string());
+        let mut commented = with_params(piece("psi:trick_blink", 1, 1), &[("_distance", 3)]);
+        commented.data.comment = Some("blink forward".to_string());
+        let mut spell = spell(
+            "json",
+            vec![
+                constant,
+                commented,
+                piece("psi:selector_caster", 2, 1),
+                with_params(piece("psi:operator_sum", 3, 1), &[]),
+            ],
+        );
+        spell.mods = vec![Mod {
+            name: "psi".to_string(),
+            version: "r1.1-78".to_string(),
+        }];
+
+        let json = spell_to_json(&spell).unwrap();
+        assert_eq!(spell_from_json(&json).unwrap(), spell);
+    }
+
+    #[test]
+    fn constants_skip_empty_and_missing_values() {
+        let mut number = piece("psi:constant_number", 0, 0);
+        number.data.constant = Some("8".to_string());
+        let mut blank = piece("psi:constant_number", 1, 0);
+        blank.data.constant = Some(String::new());
+        let spell = spell(
+            "constants",
+            vec![number, blank, piece("psi:selector_caster", 2, 0)],
+        );
+        assert_eq!(spell.constants(), vec![((0, 0), "8".to_string())]);
+    }
+
+    // Runs in every feature set, including `--no-default-features`, where the
+    // binary form is all that is left.
+    #[test]
+    fn binary_form_needs_no_optional_feature() {
+        let mut spell = spell(
+            "s",
+            vec![with_params(
+                piece("psi:trick_debug", 1, 2),
+                &[("_target", 4)],
+            )],
+        );
+        spell.mods = vec![Mod {
+            name: "psi".to_string(),
+            version: "r1.1".to_string(),
+        }];
+        assert_eq!(
+            round_trip(&spell),
+            b"s\0psi,r1.1]\x12trick_debug\0\0\x01\x00\x04"
+        );
+    }
+
+    #[test]
+    fn encode_errors_reports_every_problem_in_order() {
+        let mut bad_key = piece("psi:trick\0debug", 0, 0);
+        bad_key.data.comment = Some("also\0bad".to_string());
+        let too_many = (0..=MAX_PARAMS).map(|i| (format!("_p{i}"), 0)).collect();
+        let mut crowded = piece("psi:operator_sum", 1, 0);
+        crowded.data.params = Some(too_many);
+        let mut spell = spell("bad\0name", vec![bad_key, crowded]);
+        spell.mods = vec![Mod {
+            name: "psi;".to_string(),
+            version: "1".to_string(),
+        }];
+
+        assert_eq!(
+            spell.encode_errors(),
+            vec![
+                EncodeError::ReservedByte {
+                    field: Field::Name,
+                    position: None,
+                    byte: 0,
+                },
+                EncodeError::ReservedByte {
+                    field: Field::ModName,
+                    position: None,
+                    byte: b';',
+                },
+                EncodeError::ReservedByte {
+                    field: Field::Key,
+                    position: Some((0, 0)),
+                    byte: 0,
+                },
+                EncodeError::ReservedByte {
+                    field: Field::Comment,
+                    position: Some((0, 0)),
+                    byte: 0,
+                },
+                EncodeError::TooManyParams {
+                    x: 1,
+                    y: 0,
+                    count: MAX_PARAMS + 1,
+                },
+            ]
+        );
+        assert_eq!(spell.bin(), Err(spell.encode_errors().remove(0)));
+    }
+
+    #[test]
+    fn encodable_spell_has_no_errors() {
+        let spell = spell(
+            "fine",
+            vec![with_params(
+                piece("psi:trick_debug", 0, 0),
+                &[("_target", 1), ("_custom", 2)],
+            )],
+        );
+        assert!(spell.encode_errors().is_empty());
+    }
+
+    #[test]
+    fn overlay_shifts_the_fragment_and_keeps_its_params() {
+        let mut base = spell("base", vec![piece("psi:selector_caster", 0, 0)]);
+        let fragment = spell(
+            "fragment",
+            vec![with_params(
+                piece("psi:trick_debug", 0, 0),
+                &[("_target", 3)],
+            )],
+        );
+        base.overlay(&fragment, 1, 0).unwrap();
+        assert_eq!(
+            base.pieces,
+            vec![
+                piece("psi:selector_caster", 0, 0),
+                with_params(piece("psi:trick_debug", 1, 0), &[("_target", 3)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlay_leaves_the_spell_alone_on_failure() {
+        let mut base = spell("base", vec![piece("psi:selector_caster", 2, 2)]);
+        let fragment = spell(
+            "fragment",
+            vec![
+                piece("psi:trick_debug", 0, 0),
+                piece("psi:trick_debug", 1, 1),
+            ],
+        );
+        let before = base.clone();
+        assert_eq!(
+            base.overlay(&fragment, 1, 1),
+            Err(OverlayError::Collision { x: 2, y: 2 })
+        );
+        assert_eq!(base, before);
+        assert_eq!(
+            base.overlay(&fragment, 255, 0),
+            Err(OverlayError::OutOfGrid { x: 256, y: 1 })
+        );
+        assert_eq!(base, before);
+    }
+
+    #[test]
+    fn comments_can_travel_out_of_band() {
+        let mut commented = piece("psi:selector_caster", 0, 0);
+        commented.data.comment = Some("who casts".to_string());
+        let spell = spell("comments", vec![commented, piece("psi:trick_debug", 1, 0)]);
+        let options = |comments| BinOptions {
+            comments,
+            ..BinOptions::default()
+        };
+
+        let (bin, comments) = spell
+            .bin_with_options(options(CommentMode::Separate))
+            .unwrap();
+        assert_eq!(comments, vec![Some("who casts".to_string()), None]);
+        let (omitted, none) = spell.bin_with_options(options(CommentMode::Omit)).unwrap();
+        assert_eq!((&omitted, none), (&bin, vec![]));
+        assert_eq!(bin, spell.without_comments().bin().unwrap());
+
+        let mut decoded = Spell::decode(&bin).unwrap();
+        decoded.attach_comments(comments);
+        assert_eq!(decoded, spell);
+    }
+
+    #[test]
+    fn into_parts_hands_back_every_field() {
+        let mut spell = spell("parts", vec![piece("psi:selector_caster", 0, 0)]);
+        spell.mods = vec![Mod {
+            name: "psi".to_string(),
+            version: "1".to_string(),
+        }];
+        let (name, mods, pieces) = spell.clone().into_parts();
+        assert_eq!(name, spell.name);
+        assert_eq!(mods, spell.mods);
+        assert_eq!(pieces, spell.pieces);
+    }
+
+    #[test]
+    fn name_length_is_counted_in_utf16_units() {
+        let named = |name: &str| spell(name, vec![]);
+        assert!(named(&"a".repeat(DEFAULT_MAX_NAME_LEN)).bin().is_ok());
+        assert!(named(&"日".repeat(DEFAULT_MAX_NAME_LEN)).bin().is_ok());
+        assert_eq!(
+            named(&"a".repeat(DEFAULT_MAX_NAME_LEN + 1)).bin(),
+            Err(EncodeError::NameTooLong { len: 21, max: 20 })
+        );
+        // Outside the Basic Multilingual Plane each character takes two units.
+        assert_eq!(
+            named(&"🪄".repeat(11)).bin(),
+            Err(EncodeError::NameTooLong { len: 22, max: 20 })
+        );
+
+        let relaxed = BinOptions {
+            max_name_len: 64,
+            ..BinOptions::default()
+        };
+        assert!(named(&"a".repeat(64)).bin_with_options(relaxed).is_ok());
+    }
+
+    #[test]
+    fn params_are_written_in_name_order() {
+        let spell = spell(
+            "order",
+            vec![with_params(
+                piece("psi:operator_sum", 0, 0),
+                &[("_number2", 1), ("_custom", 2), ("_number1", 3)],
+            )],
+        );
+        assert_eq!(
+            round_trip(&spell),
+            b"order\0]\x00operator_sum\0\0\x03\xff_custom\0\x02\x02\x03\x03\x01"
+        );
+    }
+
+    #[test]
+    fn params_read_out_of_order_iterate_by_name() {
+        // _number2 before _number1, as an older encoder could write them.
+        let written = b"s\0]\x00operator_sum\0\0\x02\x03\x01\x02\x03";
+        let decoded = Spell::decode(written).unwrap();
+        let params = decoded.pieces[0].data.params.as_ref().unwrap();
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            [(&"_number1".to_string(), &3), (&"_number2".to_string(), &1)]
+        );
+        assert_eq!(
+            decoded.bin().unwrap(),
+            b"s\0]\x00operator_sum\0\0\x02\x02\x03\x03\x01"
+        );
+    }
+
+    #[test]
+    fn strip_comments_keeps_everything_else() {
+        let mut commented = with_params(piece("psi:trick_debug", 1, 0), &[("_target", 3)]);
+        commented.data.comment = Some("print it".to_string());
+        let mut spell = spell("strip", vec![commented]);
+        let stripped = spell.without_comments();
+        assert_eq!(stripped.pieces[0].data.comment, None);
+
+        spell.strip_comments();
+        assert_eq!(spell, stripped);
+        assert_eq!(
+            spell.pieces,
+            vec![with_params(
+                piece("psi:trick_debug", 1, 0),
+                &[("_target", 3)]
+            )]
+        );
+    }
+
+    #[test]
+    fn prune_unused_mods_keeps_mods_with_pieces() {
+        let listed = |name: &str| Mod {
+            name: name.to_string(),
+            version: "1".to_string(),
+        };
+        let mut spell = spell(
+            "mods",
+            vec![
+                piece("selector_caster", 0, 0),
+                piece("phi:trick_used", 1, 0),
+            ],
+        );
+        spell.mods = vec![listed("psi"), listed("phi"), listed("unused")];
+        spell.prune_unused_mods();
+        assert_eq!(spell.mods, vec![listed("psi"), listed("phi")]);
+
+        spell.pieces.remove(0);
+        spell.prune_unused_mods();
+        assert_eq!(spell.mods, vec![listed("phi")]);
+    }
+
+    #[test]
+    fn psi_key_with_a_second_namespace_is_refused() {
+        let ambiguous = EncodeError::AmbiguousKey { x: 1, y: 0 };
+        for key in ["psi:phi:trick_phase", "psi:psi:connector"] {
+            let spell = spell("ns", vec![piece("psi:connector", 0, 0), piece(key, 1, 0)]);
+            assert_eq!(spell.encode_errors(), vec![ambiguous.clone()]);
+            assert_eq!(spell.bin(), Err(ambiguous.clone()));
+        }
+
+        for key in ["psi:x", "phi:x", "x", "phi:psi:x"] {
+            let spell = spell("ns", vec![piece(key, 0, 0)]);
+            assert_eq!(spell.encode_errors(), vec![], "{key}");
+        }
+    }
+
+    #[test]
+    fn only_the_psi_namespace_is_left_implicit() {
+        let spell = spell(
+            "ns",
+            vec![piece("psi:connector", 0, 0), piece("phi:trick_phase", 1, 0)],
+        );
+        assert_eq!(
+            round_trip(&spell),
+            b"ns\0]\x00connector\0\0\xfe\x10phi:trick_phase\0\0\xfe"
+        );
+        let data = &spell.pieces[1].data;
+        assert_eq!((data.namespace(), data.path()), ("phi", "trick_phase"));
+    }
+
+    #[test]
+    fn repeated_custom_params_go_through_the_table() {
+        let shared = |x| with_params(piece("psi:connector", x, 0), &[("_shared_param", 1)]);
+        let spell = spell("t", vec![shared(0), shared(1), shared(2)]);
+        let mut expected = b"\x01\x02t\0]\x01_shared_param\0".to_vec();
+        for x in 0..3 {
+            expected.push(x << 4);
+            expected.extend_from_slice(b"connector\0\0\x01\xfe\x00\x01");
+        }
+        assert_eq!(round_trip(&spell), expected);
+    }
+
+    #[test]
+    fn header_flags_combine() {
+        let shared = |x| with_params(piece("psi:connector", x, 16), &[("_shared_param", 1)]);
+        let spell = spell("t", vec![shared(0), shared(1), shared(2)]);
+        assert_eq!(round_trip(&spell)[..2], [HEADER_TAG, 0b11]);
+    }
+
+    #[test]
+    fn absent_and_empty_params_stay_distinct() {
+        let absent = spell("p", vec![piece("psi:connector", 0, 0)]);
+        let empty = spell("p", vec![with_params(piece("psi:connector", 0, 0), &[])]);
+        assert_ne!(round_trip(&absent), round_trip(&empty));
+        assert_eq!(
+            Spell::decode(&empty.bin().unwrap()).unwrap().pieces[0]
+                .data
+                .params,
+            Some(SpellParams::new())
+        );
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn nbt_bytes_round_trip_in_every_flavor() {
+        let spell = spell(
+            "nbt",
+            vec![with_params(
+                piece("psi:trick_debug", 1, 0),
+                &[("_target", 3)],
+            )],
+        );
+        for flavor in [
+            Flavor::Uncompressed,
+            Flavor::ZlibCompressed,
+            Flavor::GzCompressed,
+        ] {
+            let bytes = spell_to_nbt_bytes(&spell, flavor).unwrap();
+            assert_eq!(nbt_bytes_to_spell(&bytes, flavor).unwrap(), spell);
+        }
+    }
+
+    #[cfg(feature = "snbt")]
+    const DEBUG_SNBT: &str = r#"{spellName:"debug",modsRequired:[{modName:"psi",modVersion:"r1.1-78"}],spellList:[{x:0,y:0,data:{key:"psi:selector_caster",params:{}}},{x:1,y:0,data:{key:"psi:trick_debug",params:{_target:3}}}]}"#;
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn item_nbt_finds_the_spell_wherever_it_is_kept() {
+        let spell = Spell::from_snbt(DEBUG_SNBT).unwrap();
+        for item in [
+            DEBUG_SNBT.to_string(),
+            format!(r#"{{id:"psi:spell_drive",Count:1b,tag:{{spell:{DEBUG_SNBT}}}}}"#),
+            format!(r#"{{spellData:{DEBUG_SNBT}}}"#),
+        ] {
+            assert_eq!(spell_from_item_nbt(&item).unwrap(), spell, "{item}");
+        }
+        assert!(matches!(
+            spell_from_item_nbt(r#"{id:"psi:spell_drive",tag:{}}"#),
+            Err(Error::NoSpellInItem)
+        ));
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn snbt_errors_point_at_the_line() {
+        let snbt = "{\n  spellName: \"broken\",\n  spellList: [}\n}";
+        let err = Spell::from_snbt(snbt).unwrap_err();
+        assert!(
+            matches!(err, Error::Snbt { line: 3, .. }),
+            "expected an error on line 3, got {err:?}"
+        );
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn snbt_names_the_missing_field() {
+        assert!(matches!(
+            Spell::from_snbt(r#"{spellName:"no pieces"}"#),
+            Err(Error::MissingField("spellList"))
+        ));
+        assert!(matches!(
+            Spell::from_snbt("{spellList:[]}"),
+            Err(Error::MissingField("spellName"))
+        ));
+    }
+
+    #[test]
+    fn check_encodable_agrees_with_bin() {
+        let mut both = with_params(piece("psi:constant_number", 2, 3), &[("_target", 1)]);
+        both.data.constant = Some("1".to_string());
+        let bad = spell("dry run", vec![both]);
+        assert_eq!(
+            bad.check_encodable(),
+            Err(EncodeError::ConstantWithParams { x: 2, y: 3 })
+        );
+        assert_eq!(bad.check_encodable().err(), bad.bin().err());
+
+        let good = spell("dry run", vec![piece("psi:connector", 0, 0)]);
+        assert_eq!(good.check_encodable(), Ok(()));
+        assert!(good.bin().is_ok());
+    }
+
+    #[test]
+    fn distinct_keys_are_listed_once_in_order() {
+        let spell = spell(
+            "keys",
+            vec![
+                piece("psi:trick_debug", 0, 0),
+                piece("psi:connector", 1, 0),
+                piece("psi:trick_debug", 2, 0),
+            ],
+        );
+        assert_eq!(
+            spell.distinct_keys().into_iter().collect::<Vec<_>>(),
+            ["psi:connector", "psi:trick_debug"]
+        );
+    }
+
+    #[test]
+    fn compact_positions_shifts_to_the_origin_keeping_gaps() {
+        let mut shifted = spell(
+            "compact",
+            vec![
+                with_params(piece("psi:trick_debug", 5, 3), &[("_target", 3)]),
+                piece("psi:selector_caster", 3, 7),
+            ],
+        );
+        shifted.compact_positions();
+        let positions: Vec<_> = shifted.pieces.iter().map(Piece::position).collect();
+        assert_eq!(positions, [(2, 0), (0, 4)]);
+        assert_eq!(
+            shifted.pieces[0].data.params.as_ref().unwrap()["_target"],
+            3
+        );
+
+        let mut empty = spell("empty", vec![]);
+        empty.compact_positions();
+        assert!(empty.pieces.is_empty());
+    }
+
+    // As Psi's programmer copies it, uuid and all, tidied onto several lines.
+    #[cfg(feature = "snbt")]
+    const BLINK_SNBT: &str = r#"{
+        modsRequired: [{modName: "psi", modVersion: "r1.1-78"}, {modName: "phi", modVersion: "1.2"}],
+        spellList: [
+            {data: {key: "psi:selector_caster", params: {}}, x: 3, y: 4},
+            {data: {comment: "how far", constantValue: "8", key: "psi:constant_number", params: {}}, x: 4, y: 3},
+            {data: {key: "psi:trick_blink", params: {_target: 3, _distance: 1}}, x: 4, y: 4},
+            {data: {key: "phi:trick_phase"}, x: 5, y: 4}
+        ],
+        spellName: "blink",
+        uuidLeast: -5772776249439291336L,
+        uuidMost: 7164426575842085815L
+    }"#;
+
+    #[cfg(feature = "snbt")]
+    fn blink() -> Spell {
+        let mut distance = with_params(piece("psi:constant_number", 4, 3), &[]);
+        distance.data.constant = Some("8".to_string());
+        distance.data.comment = Some("how far".to_string());
+        let mut spell = spell(
+            "blink",
+            vec![
+                with_params(piece("psi:selector_caster", 3, 4), &[]),
+                distance,
+                with_params(
+                    piece("psi:trick_blink", 4, 4),
+                    &[("_target", 3), ("_distance", 1)],
+                ),
+                piece("phi:trick_phase", 5, 4),
+            ],
+        );
+        spell.mods = vec![
+            Mod {
+                name: "psi".to_string(),
+                version: "r1.1-78".to_string(),
+            },
+            Mod {
+                name: "phi".to_string(),
+                version: "1.2".to_string(),
+            },
+        ];
+        spell
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn snbt_from_the_programmer_parses_every_piece_shape() {
+        assert_eq!(Spell::from_snbt(BLINK_SNBT).unwrap(), blink());
+        assert_eq!(clipboard_to_spell(BLINK_SNBT).unwrap(), blink());
+        assert_eq!(wasm::snbt_to_spell(BLINK_SNBT).ok(), Some(blink()));
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn snbt_survives_the_binary_form() {
+        let decoded = Spell::decode(&blink().bin().unwrap()).unwrap();
+        // The binary form writes a constant in place of params, empty or not.
+        let mut expected = blink();
+        expected.pieces[1].data.params = None;
+        assert_eq!(decoded, expected);
+        assert_eq!(
+            Spell::from_snbt(&decoded.to_snbt().unwrap()).unwrap(),
+            decoded
+        );
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn snbt_round_trips_through_to_snbt() {
+        assert_eq!(
+            Spell::from_snbt(&blink().to_snbt().unwrap()).unwrap(),
+            blink()
+        );
+        assert_eq!(
+            clipboard_to_spell(&spell_to_clipboard(&blink()).unwrap()).unwrap(),
+            blink()
+        );
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn snbt_without_mods_or_pieces_is_an_empty_spell() {
+        assert_eq!(
+            Spell::from_snbt(r#"{spellName: "empty", spellList: []}"#).unwrap(),
+            spell("empty", vec![])
+        );
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn snbt_params_keep_custom_names_and_side_numbers() {
+        let snbt = r#"{spellName: "custom", spellList: [
+            {data: {key: "psi:operator_sum", params: {_number1: 3, _my_param: 4, _target: 0}}, x: 1, y: 0}
+        ]}"#;
+        let spell = Spell::from_snbt(snbt).unwrap();
+        assert_eq!(
+            spell.pieces,
+            vec![with_params(
+                piece("psi:operator_sum", 1, 0),
+                &[("_number1", 3), ("_my_param", 4), ("_target", 0)],
+            )]
+        );
+        round_trip(&spell);
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn clipboard_tolerates_surrounding_whitespace() {
+        let pasted = format!("\n  {DEBUG_SNBT}\r\n");
+        assert_eq!(
+            clipboard_to_spell(&pasted).unwrap(),
+            Spell::from_snbt(DEBUG_SNBT).unwrap()
+        );
+        let spell = Spell::from_snbt(DEBUG_SNBT).unwrap();
+        assert_eq!(
+            spell_to_clipboard(&spell).unwrap(),
+            spell.to_snbt().unwrap()
+        );
+    }
+
+    #[test]
+    fn constant_next_to_params_is_refused_not_dropped() {
+        let with_constant = |params: &[(&str, u8)]| {
+            let mut number = with_params(piece("psi:constant_number", 1, 1), params);
+            number.data.constant = Some("4".to_string());
+            spell("both", vec![number])
+        };
+        assert_eq!(
+            with_constant(&[("_target", 2)]).bin(),
+            Err(EncodeError::ConstantWithParams { x: 1, y: 1 })
+        );
+        // Psi writes an empty params compound next to every constant.
+        let decoded = Spell::decode(&with_constant(&[]).bin().unwrap()).unwrap();
+        assert_eq!(decoded.pieces[0].data.constant.as_deref(), Some("4"));
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn json_schema_uses_the_serialized_field_names() {
+        let schema: serde_json::Value =
+            serde_json::from_str(&spell_json_schema().unwrap()).unwrap();
+        let properties = &schema["properties"];
+        for field in ["spellName", "spellList", "modsRequired"] {
+            assert!(properties.get(field).is_some(), "missing {field}");
+        }
+        let data = &schema["definitions"]["SpellData"]["properties"];
+        for field in ["key", "params", "constantValue", "comment", "extra"] {
+            assert!(data.get(field).is_some(), "missing {field}");
+        }
+    }
 }
@@ -0,0 +1,105 @@
+use alloc::string::String;
+
+use crate::SpellParams;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    PreferLeft,
+    PreferRight,
+    ErrorOnConflict,
+}
+
+/// A param both maps set to different sides. The same name on the same side is
+/// not a conflict under any policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamConflict {
+    pub name: String,
+    pub left: u8,
+    pub right: u8,
+}
+
+impl core::fmt::Display for ParamConflict {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "param `{}` is on side {} in one map and side {} in the other",
+            self.name, self.left, self.right
+        )
+    }
+}
+
+impl core::error::Error for ParamConflict {}
+
+/// Every param from both maps. Only `ErrorOnConflict` can fail.
+pub fn merge_params(
+    a: &SpellParams,
+    b: &SpellParams,
+    policy: MergePolicy,
+) -> Result<SpellParams, ParamConflict> {
+    let mut merged = a.clone();
+    for (name, &right) in b {
+        match merged.get(name) {
+            Some(&left) if left != right => match policy {
+                MergePolicy::PreferLeft => {}
+                MergePolicy::PreferRight => {
+                    merged.insert(name.clone(), right);
+                }
+                MergePolicy::ErrorOnConflict => {
+                    return Err(ParamConflict {
+                        name: name.clone(),
+                        left,
+                        right,
+                    })
+                }
+            },
+            Some(_) => {}
+            None => {
+                merged.insert(name.clone(), right);
+            }
+        }
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, u8)]) -> SpellParams {
+        pairs
+            .iter()
+            .map(|(name, side)| (name.to_string(), *side))
+            .collect()
+    }
+
+    #[test]
+    fn policy_decides_conflicts() {
+        let left = params(&[("_number1", 1), ("_number2", 2)]);
+        let right = params(&[("_number2", 4), ("_number3", 3)]);
+        assert_eq!(
+            merge_params(&left, &right, MergePolicy::PreferLeft),
+            Ok(params(&[("_number1", 1), ("_number2", 2), ("_number3", 3)]))
+        );
+        assert_eq!(
+            merge_params(&left, &right, MergePolicy::PreferRight),
+            Ok(params(&[("_number1", 1), ("_number2", 4), ("_number3", 3)]))
+        );
+        assert_eq!(
+            merge_params(&left, &right, MergePolicy::ErrorOnConflict),
+            Err(ParamConflict {
+                name: "_number2".to_string(),
+                left: 2,
+                right: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn same_side_is_never_a_conflict() {
+        let shared = params(&[("_target", 3)]);
+        assert_eq!(
+            merge_params(&shared, &shared, MergePolicy::ErrorOnConflict),
+            Ok(shared)
+        );
+    }
+}
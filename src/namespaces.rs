@@ -0,0 +1,97 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::Spell;
+
+/// How the declared mods line up with the namespaces the pieces actually use.
+/// `psi` itself is never reported missing, since no spell has to declare it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModUsage {
+    pub undeclared: BTreeSet<String>,
+    pub unused: BTreeSet<String>,
+}
+
+impl ModUsage {
+    pub fn is_consistent(&self) -> bool {
+        self.undeclared.is_empty() && self.unused.is_empty()
+    }
+}
+
+impl Spell {
+    pub fn required_namespaces(&self) -> HashSet<String> {
+        self.pieces
+            .iter()
+            .map(|p| p.data.namespace().to_owned())
+            .collect()
+    }
+
+    /// The read-only counterpart of `prune_unused_mods`: `unused` holds exactly
+    /// the mods it would drop.
+    pub fn mod_usage(&self) -> ModUsage {
+        let required = self.required_namespaces();
+        let declared: HashSet<&str> = self.mods.iter().map(|m| m.name.as_str()).collect();
+
+        ModUsage {
+            undeclared: required
+                .iter()
+                .filter(|ns| *ns != "psi" && !declared.contains(ns.as_str()))
+                .cloned()
+                .collect(),
+            unused: declared
+                .into_iter()
+                .filter(|name| !required.contains(*name))
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn declared(spell: &mut Spell, names: &[&str]) {
+        spell.mods = names
+            .iter()
+            .map(|name| crate::Mod {
+                name: name.to_string(),
+                version: "1".to_string(),
+            })
+            .collect();
+    }
+
+    fn set(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn usage_compares_declared_mods_with_piece_namespaces() {
+        let mut spell = spell(
+            "mods",
+            vec![
+                piece("connector", 0, 0),
+                piece("phi:trick_a", 1, 0),
+                piece("rho:trick_b", 2, 0),
+            ],
+        );
+        declared(&mut spell, &["phi", "unused"]);
+        assert_eq!(
+            spell.required_namespaces(),
+            HashSet::from(["psi", "phi", "rho"].map(str::to_string))
+        );
+        let usage = spell.mod_usage();
+        assert_eq!(usage.undeclared, set(&["rho"]));
+        assert_eq!(usage.unused, set(&["unused"]));
+        assert!(!usage.is_consistent());
+    }
+
+    #[test]
+    fn unused_is_what_prune_would_drop() {
+        let mut spell = spell("mods", vec![piece("phi:trick_a", 0, 0)]);
+        declared(&mut spell, &["psi", "phi", "rho"]);
+        let unused = spell.mod_usage().unused;
+        spell.prune_unused_mods();
+        assert!(spell.mod_usage().is_consistent());
+        assert_eq!(unused, set(&["psi", "rho"]));
+    }
+}
@@ -0,0 +1,174 @@
+use alloc::{borrow::Cow, string::String, vec::Vec};
+
+use crate::{Piece, Spell};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// Drops pieces whose namespace is neither `psi` nor one of the spell's mods.
+    pub strip_unknown_namespaces: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NormalizeReport {
+    pub prefixed: Vec<(u8, u8)>,
+    pub snake_cased: Vec<(u8, u8)>,
+    pub stripped: Vec<Piece>,
+}
+
+fn snake_case(path: &str) -> String {
+    let mut out = String::with_capacity(path.len() + 4);
+    for (i, c) in path.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i != 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// Bare keys are `psi` keys, as everywhere else.
+fn qualified(key: &str) -> Cow<'_, str> {
+    if key.contains(':') {
+        Cow::Borrowed(key)
+    } else {
+        Cow::Owned(format!("psi:{key}"))
+    }
+}
+
+impl Spell {
+    /// Matches `from` with or without its `psi:` prefix, and writes `to` with
+    /// one. Returns how many pieces changed.
+    pub fn replace_key(&mut self, from: &str, to: &str) -> usize {
+        let (from, to) = (qualified(from), qualified(to));
+        let mut replaced = 0;
+        for piece in &mut self.pieces {
+            if qualified(&piece.data.key) == from {
+                piece.data.key = to.clone().into_owned();
+                replaced += 1;
+            }
+        }
+        replaced
+    }
+
+    /// Rewrites legacy keys into the `psi:snake_case` form current Psi uses. Keys in
+    /// another mod's namespace are never rewritten, only stripped when requested.
+    pub fn normalize_keys(&mut self, opts: NormalizeOptions) -> NormalizeReport {
+        let mut report = NormalizeReport::default();
+        let mods = &self.mods;
+
+        let mut pieces = Vec::with_capacity(self.pieces.len());
+        for mut piece in core::mem::take(&mut self.pieces) {
+            let position = (piece.x, piece.y);
+            let key = &mut piece.data.key;
+
+            match key.split_once(':') {
+                Some(("psi", path)) => {
+                    if path.bytes().any(|b| b.is_ascii_uppercase()) {
+                        *key = format!("psi:{}", snake_case(path));
+                        report.snake_cased.push(position);
+                    }
+                }
+                Some((namespace, _)) => {
+                    if opts.strip_unknown_namespaces && !mods.iter().any(|m| m.name == namespace) {
+                        report.stripped.push(piece);
+                        continue;
+                    }
+                }
+                None => {
+                    let path = snake_case(key);
+                    if path != *key {
+                        report.snake_cased.push(position);
+                    }
+                    *key = format!("psi:{path}");
+                    report.prefixed.push(position);
+                }
+            }
+
+            pieces.push(piece);
+        }
+        self.pieces = pieces;
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn legacy_keys_become_prefixed_snake_case() {
+        let mut spell = spell(
+            "legacy",
+            vec![
+                piece("selectorCaster", 0, 0),
+                piece("psi:trickDebug", 1, 0),
+                piece("connector", 2, 0),
+                piece("psi:operator_sum", 3, 0),
+            ],
+        );
+        let report = spell.normalize_keys(NormalizeOptions::default());
+        let keys: Vec<_> = spell.pieces.iter().map(|p| p.data.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "psi:selector_caster",
+                "psi:trick_debug",
+                "psi:connector",
+                "psi:operator_sum"
+            ]
+        );
+        assert_eq!(report.prefixed, vec![(0, 0), (2, 0)]);
+        assert_eq!(report.snake_cased, vec![(0, 0), (1, 0)]);
+        assert!(report.stripped.is_empty());
+    }
+
+    #[test]
+    fn other_namespaces_are_kept_unless_stripping() {
+        let foreign = piece("rpsideas:trickConjure", 0, 0);
+        let listed = piece("phi:trick_listed", 1, 0);
+        let mut spell = spell("mods", vec![foreign.clone(), listed.clone()]);
+        spell.mods = vec![crate::Mod {
+            name: "phi".to_string(),
+            version: "1".to_string(),
+        }];
+
+        let mut kept = spell.clone();
+        kept.normalize_keys(NormalizeOptions::default());
+        assert_eq!(kept, spell);
+
+        let report = spell.normalize_keys(NormalizeOptions {
+            strip_unknown_namespaces: true,
+        });
+        assert_eq!(report.stripped, vec![foreign]);
+        assert_eq!(spell.pieces, vec![listed]);
+    }
+
+    #[test]
+    fn replace_key_matches_with_or_without_the_prefix() {
+        let mut spell = spell(
+            "rename",
+            vec![
+                piece("psi:trick_blink", 0, 0),
+                piece("trick_blink", 1, 0),
+                piece("phi:trick_blink", 2, 0),
+            ],
+        );
+        assert_eq!(spell.replace_key("trick_blink", "trick_teleport"), 2);
+        let keys: Vec<_> = spell.pieces.iter().map(|p| p.data.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "psi:trick_teleport",
+                "psi:trick_teleport",
+                "phi:trick_blink"
+            ]
+        );
+        assert_eq!(spell.replace_key("psi:missing", "psi:other"), 0);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{DecodeError, Field};
+
+// Index-based cursor over the encoded bytes, in place of a `std::io` cursor.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    pub(crate) fn next(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self.data.get(self.pos).ok_or(DecodeError::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    // Returns the bytes before `byte` and consumes the delimiter. A field that
+    // runs into the end of the input without its delimiter is truncated.
+    pub(crate) fn read_until(&mut self, byte: u8) -> Result<&'a [u8], DecodeError> {
+        let rest = &self.data[self.pos..];
+        let len = rest
+            .iter()
+            .position(|b| *b == byte)
+            .ok_or(DecodeError::UnexpectedEof)?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+
+    pub(crate) fn read_until_nul(&mut self) -> Result<&'a [u8], DecodeError> {
+        self.read_until(0)
+    }
+
+    pub(crate) fn read_str_until_nul(&mut self, field: Field) -> Result<&'a str, DecodeError> {
+        let bytes = self.read_until_nul()?;
+        self.utf8(bytes, field)
+    }
+
+    // `bytes` must have come from this reader, so a bad byte can be reported
+    // by its offset in the whole input.
+    pub(crate) fn utf8(&self, bytes: &'a [u8], field: Field) -> Result<&'a str, DecodeError> {
+        core::str::from_utf8(bytes).map_err(|e| DecodeError::Utf8 {
+            field,
+            offset: bytes.as_ptr() as usize - self.data.as_ptr() as usize + e.valid_up_to(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_until_consumes_the_delimiter() {
+        let mut reader = Reader::new(b"ab\0c\0");
+        assert_eq!(reader.read_until_nul().unwrap(), b"ab");
+        assert_eq!(reader.pos(), 3);
+        assert_eq!(reader.read_until_nul().unwrap(), b"c");
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn field_missing_its_delimiter_is_an_error() {
+        let mut reader = Reader::new(b"abc");
+        assert_eq!(reader.read_until_nul(), Err(DecodeError::UnexpectedEof));
+        // Nothing was consumed, the last byte included.
+        assert_eq!(reader.pos(), 0);
+        assert_eq!(reader.next().unwrap(), b'a');
+    }
+
+    #[test]
+    fn unterminated_name_fails_the_decode() {
+        assert_eq!(
+            crate::Spell::decode(b"blink"),
+            Err(DecodeError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn utf8_errors_name_the_field_and_offset() {
+        assert_eq!(
+            crate::Spell::decode(b"s\0]\x00connector\0ok \xff\0\xfe"),
+            Err(DecodeError::Utf8 {
+                field: Field::Comment,
+                offset: 17,
+            })
+        );
+        assert_eq!(
+            crate::Spell::decode(b"s\0psi,r\xc3;]"),
+            Err(DecodeError::Utf8 {
+                field: Field::ModVersion,
+                offset: 7,
+            })
+        );
+    }
+}
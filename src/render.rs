@@ -0,0 +1,205 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+};
+use core::fmt::Write;
+
+use crate::{Side, Spell};
+
+const CELL_WIDTH: usize = 3;
+// SVG cell size and the gap between cells, in user units. Connection arrows
+// fill the gaps.
+const SVG_CELL: i32 = 48;
+const SVG_GAP: i32 = 16;
+
+fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// Centre of the cell at (x, y).
+fn svg_center(x: u8, y: u8) -> (i32, i32) {
+    let at = |i: u8| SVG_GAP + i as i32 * (SVG_CELL + SVG_GAP) + SVG_CELL / 2;
+    (at(x), at(y))
+}
+
+// Initials of the underscore-separated words in the key, namespace dropped:
+// `psi:trick_add_motion` becomes `tam`, `psi:connector` becomes `c`.
+fn symbol(key: &str) -> String {
+    let path = key.rsplit(':').next().unwrap_or(key);
+    path.split('_')
+        .filter_map(|word| word.chars().next())
+        .take(CELL_WIDTH)
+        .collect()
+}
+
+impl Spell {
+    pub fn render_ascii(&self) -> String {
+        let cells: BTreeMap<_, _> = self
+            .pieces
+            .iter()
+            .map(|p| ((p.y, p.x), symbol(&p.data.key)))
+            .collect();
+        let Some(width) = self.pieces.iter().map(|p| p.x).max() else {
+            return String::new();
+        };
+        let height = self.pieces.iter().map(|p| p.y).max().unwrap_or(0);
+
+        let mut out = String::new();
+        for y in 0..=height {
+            let mut row = String::new();
+            for x in 0..=width {
+                let cell = cells.get(&(y, x)).map_or(".", String::as_str);
+                row.push_str(&format!("{cell:<CELL_WIDTH$} "));
+            }
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+
+        out
+    }
+
+    /// One labelled box per piece, with its full key as a tooltip, and an arrow
+    /// into each param from the neighbour it reads. Params pointing at empty
+    /// cells or off the grid get no arrow; `validate_routing` reports those.
+    pub fn to_svg(&self) -> String {
+        let width = self
+            .pieces
+            .iter()
+            .map(|p| p.x)
+            .max()
+            .map_or(0, |x| x as i32 + 1);
+        let height = self
+            .pieces
+            .iter()
+            .map(|p| p.y)
+            .max()
+            .map_or(0, |y| y as i32 + 1);
+        let size = |cells: i32| SVG_GAP + cells * (SVG_CELL + SVG_GAP);
+        let occupied: BTreeSet<_> = self.pieces.iter().map(|p| (p.x, p.y)).collect();
+
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = size(width),
+            h = size(height),
+        );
+        out.push_str(concat!(
+            r#"<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" "#,
+            r#"markerWidth="6" markerHeight="6" orient="auto-start-reverse">"#,
+            r#"<path d="M 0 0 L 10 5 L 0 10 z"/></marker></defs>"#,
+        ));
+
+        for piece in &self.pieces {
+            let (cx, cy) = svg_center(piece.x, piece.y);
+            let _ = write!(
+                out,
+                concat!(
+                    r#"<g><title>{key}</title>"#,
+                    r#"<rect x="{x}" y="{y}" width="{cell}" height="{cell}" rx="4" fill="white" stroke="black"/>"#,
+                    r#"<text x="{cx}" y="{cy}" text-anchor="middle" dominant-baseline="central" font-family="monospace">{label}</text></g>"#,
+                ),
+                key = escape_xml(&piece.data.key),
+                x = cx - SVG_CELL / 2,
+                y = cy - SVG_CELL / 2,
+                cell = SVG_CELL,
+                cx = cx,
+                cy = cy,
+                label = escape_xml(&symbol(&piece.data.key)),
+            );
+
+            for side in piece.data.params.iter().flat_map(|p| p.values()) {
+                let Some((dx, dy)) = Side::try_from(*side).ok().and_then(Side::offset) else {
+                    continue;
+                };
+                let source = (
+                    piece.x.checked_add_signed(dx),
+                    piece.y.checked_add_signed(dy),
+                );
+                let (Some(sx), Some(sy)) = source else {
+                    continue;
+                };
+                if !occupied.contains(&(sx, sy)) {
+                    continue;
+                }
+
+                let (dx, dy) = (dx as i32 * SVG_CELL / 2, dy as i32 * SVG_CELL / 2);
+                let (sx, sy) = svg_center(sx, sy);
+                let _ = write!(
+                    out,
+                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="black" marker-end="url(#arrow)"/>"#,
+                    sx - dx,
+                    sy - dy,
+                    cx + dx,
+                    cy + dy,
+                );
+            }
+        }
+
+        out.push_str("</svg>");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::*;
+
+    #[test]
+    fn ascii_marks_empty_cells_and_abbreviates_keys() {
+        let spell = spell(
+            "render",
+            vec![
+                piece("psi:selector_caster", 0, 0),
+                piece("psi:trick_add_motion", 2, 1),
+            ],
+        );
+        assert_eq!(spell.render_ascii(), "sc  .   .\n.   .   tam\n");
+    }
+
+    #[test]
+    fn ascii_of_an_empty_spell_is_empty() {
+        assert_eq!(spell("empty", vec![]).render_ascii(), "");
+    }
+
+    #[test]
+    fn svg_draws_an_arrow_only_for_connected_params() {
+        let spell = spell(
+            "svg",
+            vec![
+                piece("psi:selector_caster", 0, 0),
+                with_params(
+                    piece("psi:trick_debug", 1, 0),
+                    &[("_target", 3), ("_number", 2)],
+                ),
+            ],
+        );
+        let svg = spell.to_svg();
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="144" height="80""#)
+        );
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect ").count(), 2);
+        // Only the left side holds a piece; the bottom one is empty.
+        assert_eq!(svg.matches("<line ").count(), 1);
+        assert!(svg.contains(r#"<line x1="64" y1="40" x2="80" y2="40""#));
+    }
+
+    #[test]
+    fn svg_escapes_keys() {
+        let spell = spell("svg", vec![piece("mod:<b>&\"x\"", 0, 0)]);
+        let svg = spell.to_svg();
+        assert!(svg.contains("<title>mod:&lt;b&gt;&amp;&quot;x&quot;</title>"));
+        assert!(!svg.contains("<b>"));
+    }
+}
@@ -0,0 +1,165 @@
+use alloc::string::String;
+
+use crate::{
+    format::{self, Layout, BUILTIN_PARAMS},
+    EncodeError, Spell,
+};
+
+/// Bytes each part of the spell takes up in `Spell::bin`, delimiters included,
+/// so the fields always add up to the encoded length.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeBreakdown {
+    pub header: usize,
+    pub name: usize,
+    pub mods: usize,
+    pub param_table: usize,
+    pub positions: usize,
+    pub keys: usize,
+    pub comments: usize,
+    /// Includes the tag byte of pieces without params or constant.
+    pub params: usize,
+    pub constants: usize,
+}
+
+impl SizeBreakdown {
+    pub fn total(&self) -> usize {
+        self.header
+            + self.name
+            + self.mods
+            + self.param_table
+            + self.positions
+            + self.keys
+            + self.comments
+            + self.params
+            + self.constants
+    }
+}
+
+impl Spell {
+    pub fn size_breakdown(&self) -> Result<SizeBreakdown, EncodeError> {
+        if let Some(err) = self.encode_errors().into_iter().next() {
+            return Err(err);
+        }
+
+        Ok(self.measure(&Layout::of(&self.pieces)))
+    }
+
+    // Also what `bin` reserves up front: exact with inline comments, an upper
+    // bound without them.
+    pub(crate) fn measure(&self, layout: &Layout) -> SizeBreakdown {
+        let mut size = SizeBreakdown {
+            header: if layout.flags() != 0 { 2 } else { 0 },
+            name: self.name.len() + 1,
+            mods: self
+                .mods
+                .iter()
+                .map(|m| m.name.len() + m.version.len() + 2)
+                .sum::<usize>()
+                .max(1),
+            param_table: layout.param_table.as_ref().map_or(0, |table| {
+                1 + table.iter().map(|n| n.len() + 1).sum::<usize>()
+            }),
+            ..Default::default()
+        };
+
+        for piece in &self.pieces {
+            let data = &piece.data;
+            size.positions += layout.position_len();
+            size.keys += format::stored_key(&data.key).len() + 1;
+            size.comments += data.comment.as_ref().map_or(0, String::len) + 1;
+
+            if let Some(params) = format::params_to_encode(data) {
+                size.params += 1;
+                for name in params.keys() {
+                    size.params += if BUILTIN_PARAMS.contains(&name.as_str()) {
+                        2
+                    } else if layout.table_index(name).is_some() {
+                        3
+                    } else {
+                        name.len() + 3
+                    };
+                }
+            } else if let Some(constant) = &data.constant {
+                size.constants += constant.len() + 2;
+            } else {
+                size.params += 1;
+            }
+        }
+
+        size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn breakdown_adds_up_to_the_encoded_length() {
+        let mut constant = piece("psi:constant_number", 0, 0);
+        constant.data.constant = Some("12".to_string());
+        let mut commented = with_params(
+            piece("psi:trick_debug", 1, 0),
+            &[("_target", 3), ("_long_custom", 1)],
+        );
+        commented.data.comment = Some("print".to_string());
+        let shared = |x| with_params(piece("psi:connector", x, 20), &[("_long_custom", 1)]);
+        let mut spell = spell(
+            "size",
+            vec![
+                constant,
+                commented,
+                shared(2),
+                shared(3),
+                piece("phi:far", 2, 0),
+            ],
+        );
+        spell.mods = vec![crate::Mod {
+            name: "phi".to_string(),
+            version: "2".to_string(),
+        }];
+
+        let size = spell.size_breakdown().unwrap();
+        assert_eq!(size.total(), spell.bin().unwrap().len());
+        assert_eq!(
+            size,
+            SizeBreakdown {
+                header: 2,
+                name: 5,
+                mods: 6,
+                param_table: 14,
+                positions: 10,
+                keys: 16 + 12 + 10 + 10 + 8,
+                comments: 1 + 6 + 1 + 1 + 1,
+                params: 6 + 4 + 4 + 1,
+                constants: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn breakdown_refuses_what_bin_refuses() {
+        let spell = spell("bad\0", vec![]);
+        assert_eq!(spell.size_breakdown().err(), spell.bin().err());
+    }
+
+    #[test]
+    fn bin_reserves_what_it_writes() {
+        let mut commented = piece("psi:trick_debug", 20, 0);
+        commented.data.comment = Some("a long comment about the trick".to_string());
+        let spell = spell("reserve", vec![commented]);
+
+        // `bin` reserves `measure`, which `size_breakdown` reports: exact with
+        // inline comments, and room to spare when they are left out.
+        let reserved = spell.size_breakdown().unwrap().total();
+        assert_eq!(reserved, spell.bin().unwrap().len());
+        let (omitted, _) = spell
+            .bin_with_options(crate::BinOptions {
+                comments: crate::CommentMode::Omit,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(reserved > omitted.len());
+    }
+}
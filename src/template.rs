@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use crate::Spell;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    MissingSubstitution { x: u8, y: u8, name: String },
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::MissingSubstitution { x, y, name } => write!(
+                f,
+                "constant of piece at ({x}, {y}) uses `${{{name}}}`, which has no substitution"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+// Replaces every `${name}` in `constant`. A `${` without a closing brace is
+// left as it is.
+fn substitute(constant: &str, substitutions: &HashMap<String, String>) -> Result<String, String> {
+    let mut out = String::with_capacity(constant.len());
+    let mut rest = constant;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        let value = substitutions.get(name).ok_or_else(|| name.to_string())?;
+        out.push_str(&rest[..start]);
+        out.push_str(value);
+        rest = &rest[start + 3 + len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+impl Spell {
+    /// Only constants are templated; keys, comments and the name are copied
+    /// as they are.
+    pub fn apply_template(
+        &self,
+        substitutions: &HashMap<String, String>,
+    ) -> Result<Spell, TemplateError> {
+        let mut spell = self.clone();
+        for piece in &mut spell.pieces {
+            if let Some(constant) = &mut piece.data.constant {
+                *constant = substitute(constant, substitutions).map_err(|name| {
+                    TemplateError::MissingSubstitution {
+                        x: piece.x,
+                        y: piece.y,
+                        name,
+                    }
+                })?;
+            }
+        }
+        Ok(spell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn constant(value: &str) -> Spell {
+        let mut number = piece("psi:constant_number", 1, 2);
+        number.data.constant = Some(value.to_string());
+        number.data.comment = Some("${distance}".to_string());
+        spell("${name}", vec![number])
+    }
+
+    #[test]
+    fn placeholders_in_constants_are_replaced() {
+        let applied = constant("${distance}.${fraction}${")
+            .apply_template(&vars(&[("distance", "8"), ("fraction", "5")]))
+            .unwrap();
+        let data = &applied.pieces[0].data;
+        assert_eq!(data.constant.as_deref(), Some("8.5${"));
+        assert_eq!(data.comment.as_deref(), Some("${distance}"));
+        assert_eq!(applied.name, "${name}");
+    }
+
+    #[test]
+    fn missing_substitution_names_the_piece() {
+        assert_eq!(
+            constant("${distance}").apply_template(&vars(&[])),
+            Err(TemplateError::MissingSubstitution {
+                x: 1,
+                y: 2,
+                name: "distance".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,44 @@
+// Builders for the spells unit tests round-trip, so each test only spells out
+// what it is about.
+
+use crate::{Piece, Spell, SpellData};
+
+pub(crate) fn spell(name: &str, pieces: Vec<Piece>) -> Spell {
+    Spell {
+        mods: vec![],
+        pieces,
+        name: name.to_string(),
+    }
+}
+
+pub(crate) fn piece(key: &str, x: u8, y: u8) -> Piece {
+    Piece {
+        data: SpellData {
+            key: key.to_string(),
+            params: None,
+            constant: None,
+            comment: None,
+            #[cfg(feature = "json")]
+            extra: None,
+        },
+        x,
+        y,
+    }
+}
+
+pub(crate) fn with_params(mut piece: Piece, params: &[(&str, u8)]) -> Piece {
+    piece.data.params = Some(
+        params
+            .iter()
+            .map(|(name, side)| (name.to_string(), *side))
+            .collect(),
+    );
+    piece
+}
+
+// Encodes and decodes, checking the spell comes back unchanged.
+pub(crate) fn round_trip(spell: &Spell) -> Vec<u8> {
+    let bin = spell.bin().unwrap();
+    assert_eq!(&Spell::decode(&bin).unwrap(), spell);
+    bin
+}
@@ -0,0 +1,515 @@
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    compression::{
+        compress, compress_checked, compress_with_dict, decompress, decompress_with_dict, Codec,
+    },
+    Error, Spell,
+};
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base64Variant {
+    UrlSafe,
+    UrlSafeNoPad,
+    Standard,
+    StandardNoPad,
+}
+
+impl Base64Variant {
+    pub const ALL: [Self; 4] = [
+        Self::UrlSafe,
+        Self::UrlSafeNoPad,
+        Self::Standard,
+        Self::StandardNoPad,
+    ];
+
+    pub fn encode(self, bytes: &[u8]) -> String {
+        backend::encode(self, bytes)
+    }
+
+    pub fn decode(self, encoded: &str) -> Result<Vec<u8>, Base64Error> {
+        backend::decode(self, encoded)
+    }
+
+    pub fn encoded_len(self, len: usize) -> usize {
+        match self {
+            Self::UrlSafe | Self::Standard => len.div_ceil(3) * 4,
+            Self::UrlSafeNoPad | Self::StandardNoPad => (len * 4).div_ceil(3),
+        }
+    }
+}
+
+/// A string that is not base64 in the variant it was decoded as. The backends
+/// do not agree on what detail to give, so only the message is exposed.
+#[derive(Debug)]
+pub struct Base64Error(backend::DecodeError);
+
+impl std::fmt::Display for Base64Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for Base64Error {}
+
+// The pure-Rust `base64` unless `base64-simd` is on. `base64` still builds
+// then, but nothing calls it, so it never reaches the binary.
+#[cfg(not(feature = "base64-simd"))]
+mod backend {
+    use base64::{
+        engine::{general_purpose, GeneralPurpose},
+        Engine as _,
+    };
+
+    use super::{Base64Error, Base64Variant};
+
+    pub(super) type DecodeError = base64::DecodeError;
+
+    fn engine(variant: Base64Variant) -> &'static GeneralPurpose {
+        match variant {
+            Base64Variant::UrlSafe => &general_purpose::URL_SAFE,
+            Base64Variant::UrlSafeNoPad => &general_purpose::URL_SAFE_NO_PAD,
+            Base64Variant::Standard => &general_purpose::STANDARD,
+            Base64Variant::StandardNoPad => &general_purpose::STANDARD_NO_PAD,
+        }
+    }
+
+    pub(super) fn encode(variant: Base64Variant, bytes: &[u8]) -> String {
+        engine(variant).encode(bytes)
+    }
+
+    pub(super) fn decode(variant: Base64Variant, encoded: &str) -> Result<Vec<u8>, Base64Error> {
+        engine(variant).decode(encoded).map_err(Base64Error)
+    }
+}
+
+#[cfg(feature = "base64-simd")]
+mod backend {
+    use super::{Base64Error, Base64Variant};
+
+    pub(super) type DecodeError = base64_simd::Error;
+
+    fn engine(variant: Base64Variant) -> &'static base64_simd::Base64 {
+        match variant {
+            Base64Variant::UrlSafe => &base64_simd::URL_SAFE,
+            Base64Variant::UrlSafeNoPad => &base64_simd::URL_SAFE_NO_PAD,
+            Base64Variant::Standard => &base64_simd::STANDARD,
+            Base64Variant::StandardNoPad => &base64_simd::STANDARD_NO_PAD,
+        }
+    }
+
+    pub(super) fn encode(variant: Base64Variant, bytes: &[u8]) -> String {
+        engine(variant).encode_to_string(bytes)
+    }
+
+    pub(super) fn decode(variant: Base64Variant, encoded: &str) -> Result<Vec<u8>, Base64Error> {
+        engine(variant).decode_to_vec(encoded).map_err(Base64Error)
+    }
+}
+
+pub fn bytes_to_compressed_base64(bytes: &[u8], variant: Base64Variant) -> Result<String, Error> {
+    Ok(variant.encode(&compress(bytes)?))
+}
+
+/// Same as `bytes_to_compressed_base64` but with a CRC-16 in front of the
+/// compressed payload, so a mangled string fails with `ChecksumMismatch`
+/// instead of somewhere inside zstd. Every decoder accepts both forms.
+pub fn bytes_to_checked_base64(bytes: &[u8], variant: Base64Variant) -> Result<String, Error> {
+    Ok(variant.encode(&compress_checked(bytes)?))
+}
+
+/// No zstd layer: plain base64 of `Spell::bin`, for tools that compress on
+/// their own or want the bytes readable. Not interchangeable with the
+/// url-safe strings, which are always compressed.
+pub fn spell_to_raw_base64(spell: &Spell, variant: Base64Variant) -> Result<String, Error> {
+    Ok(variant.encode(&spell.bin()?))
+}
+
+pub fn raw_base64_to_spell(encoded: &str, variant: Base64Variant) -> Result<Spell, Error> {
+    Ok(Spell::decode(&variant.decode(encoded)?)?)
+}
+
+pub fn compressed_base64_to_bytes(encoded: &str, variant: Base64Variant) -> Result<Vec<u8>, Error> {
+    decompress(&variant.decode(encoded)?)
+}
+
+pub fn bytes_to_url_safe_with_dict(bytes: &[u8], dict: &[u8]) -> Result<String, Error> {
+    Ok(Base64Variant::UrlSafe.encode(&compress_with_dict(bytes, dict)?))
+}
+
+pub fn url_safe_to_bytes_with_dict(url_safe: &str, dict: &[u8]) -> Result<Vec<u8>, Error> {
+    decompress_with_dict(&Base64Variant::UrlSafe.decode(url_safe)?, dict)
+}
+
+pub fn bytes_to_url_safe_with_codec(bytes: &[u8], codec: &dyn Codec) -> Result<String, Error> {
+    Ok(Base64Variant::UrlSafe.encode(&codec.compress(bytes)?))
+}
+
+pub fn url_safe_to_bytes_with_codec(url_safe: &str, codec: &dyn Codec) -> Result<Vec<u8>, Error> {
+    codec.decompress(&Base64Variant::UrlSafe.decode(url_safe)?)
+}
+
+/// The whole payload is still decompressed; only decoding stops at the name.
+pub fn peek_url_safe_name(url_safe: &str) -> Result<String, Error> {
+    Ok(Spell::peek_name(&compressed_base64_to_bytes(
+        url_safe,
+        Base64Variant::UrlSafe,
+    )?)?)
+}
+
+/// For migrating stored strings after the embedded dictionary changes: decodes
+/// with the dictionary the string was made with and re-encodes with the current
+/// one. The spell bytes themselves are left untouched.
+pub fn recompress_url_safe_with_dict(old: &str, legacy_dict: &[u8]) -> Result<String, Error> {
+    bytes_to_compressed_base64(
+        &url_safe_to_bytes_with_dict(old, legacy_dict)?,
+        Base64Variant::UrlSafe,
+    )
+}
+
+pub fn recompress_url_safe(old: &str) -> Result<String, Error> {
+    bytes_to_compressed_base64(
+        &compressed_base64_to_bytes(old, Base64Variant::UrlSafe)?,
+        Base64Variant::UrlSafe,
+    )
+}
+
+pub fn url_safe_to_spell(url_safe: &str) -> Result<Spell, Error> {
+    Ok(Spell::decode(&url_safe_to_bytes(url_safe)?)?)
+}
+
+pub fn spell_to_url_safe(spell: &Spell) -> Result<String, Error> {
+    bytes_to_url_safe(&spell.bin()?)
+}
+
+/// One boundary crossing for a whole batch. Fails on the first spell that does
+/// not convert, without returning the ones before it.
+#[derive(Tsify, Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+pub struct Spells(pub Vec<Spell>);
+
+pub fn spells_to_url_safe(spells: &[Spell]) -> Result<Vec<String>, Error> {
+    spells.iter().map(spell_to_url_safe).collect()
+}
+
+pub fn url_safe_to_spells<S: AsRef<str>>(url_safe: &[S]) -> Result<Vec<Spell>, Error> {
+    url_safe
+        .iter()
+        .map(|s| url_safe_to_spell(s.as_ref()))
+        .collect()
+}
+
+pub fn spell_to_checked_url_safe(spell: &Spell) -> Result<String, Error> {
+    bytes_to_checked_base64(&spell.bin()?, Base64Variant::UrlSafe)
+}
+
+pub fn bytes_to_url_safe(bytes: &[u8]) -> Result<String, Error> {
+    bytes_to_compressed_base64(bytes, Base64Variant::UrlSafe)
+}
+
+pub fn url_safe_to_bytes(url_safe: &str) -> Result<Vec<u8>, Error> {
+    compressed_base64_to_bytes(url_safe, Base64Variant::UrlSafe)
+}
+
+// Length once percent-encoded, for consumers that escape everything outside
+// the URL-safe alphabet.
+fn url_encoded_len(encoded: &str) -> usize {
+    encoded
+        .bytes()
+        .map(|b| {
+            if matches!(b, b'+' | b'/' | b'=') {
+                3
+            } else {
+                1
+            }
+        })
+        .sum()
+}
+
+impl Spell {
+    /// Compresses for real but skips building the base64 string, so the result
+    /// is exact for `spellToUrlSafe`.
+    pub fn estimated_url_safe_len(&self) -> Result<usize, Error> {
+        Ok(Base64Variant::UrlSafe.encoded_len(compress(&self.bin()?)?.len()))
+    }
+}
+
+impl Spell {
+    /// The url-safe string, as `spellToUrlSafe` produces it. Parse one back
+    /// with `str::parse`:
+    ///
+    /// ```
+    /// # use psi_spell_encode_wasm::{Error, Spell};
+    /// # fn main() -> Result<(), Error> {
+    /// let spell = Spell {
+    ///     mods: vec![],
+    ///     pieces: vec![],
+    ///     name: "blink".to_string(),
+    /// };
+    /// let code = spell.to_url_safe()?;
+    /// let parsed: Spell = code.parse()?;
+    /// assert_eq!(parsed, spell);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_url_safe(&self) -> Result<String, Error> {
+        spell_to_url_safe(self)
+    }
+}
+
+impl std::str::FromStr for Spell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        url_safe_to_spell(s)
+    }
+}
+
+#[derive(Tsify, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+pub struct CompressionStats {
+    pub bin_len: usize,
+    pub compressed_len: usize,
+    pub base64_len: usize,
+}
+
+impl Spell {
+    pub fn compression_stats(&self) -> Result<CompressionStats, Error> {
+        let bin = self.bin()?;
+        let compressed_len = compress(&bin)?.len();
+        Ok(CompressionStats {
+            bin_len: bin.len(),
+            compressed_len,
+            base64_len: Base64Variant::UrlSafe.encoded_len(compressed_len),
+        })
+    }
+}
+
+pub fn shortest_url_safe(spell: &Spell) -> Result<(String, Base64Variant), Error> {
+    let compressed = compress(&spell.bin()?)?;
+
+    let shortest = Base64Variant::ALL
+        .into_iter()
+        .map(|variant| (variant.encode(&compressed), variant))
+        .min_by_key(|(encoded, _)| url_encoded_len(encoded))
+        .expect("there is at least one base64 variant");
+
+    Ok(shortest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    // RFC 4648 test vectors, plus bytes that hit the two characters where the
+    // alphabets differ. Whichever backend the build picked has to agree.
+    const VECTORS: &[(&[u8], &str, &str)] = &[
+        (b"", "", ""),
+        (b"f", "Zg==", "Zg"),
+        (b"fo", "Zm8=", "Zm8"),
+        (b"foo", "Zm9v", "Zm9v"),
+        (b"foob", "Zm9vYg==", "Zm9vYg"),
+        (b"fooba", "Zm9vYmE=", "Zm9vYmE"),
+        (b"foobar", "Zm9vYmFy", "Zm9vYmFy"),
+        (&[0xfb, 0xff], "+/8=", "+/8"),
+    ];
+
+    #[test]
+    fn backend_matches_rfc_vectors() {
+        for (bytes, padded, unpadded) in VECTORS {
+            let url_safe = padded.replace('+', "-").replace('/', "_");
+            let url_safe_no_pad = unpadded.replace('+', "-").replace('/', "_");
+            for (variant, expected) in [
+                (Base64Variant::Standard, padded.to_string()),
+                (Base64Variant::StandardNoPad, unpadded.to_string()),
+                (Base64Variant::UrlSafe, url_safe),
+                (Base64Variant::UrlSafeNoPad, url_safe_no_pad),
+            ] {
+                assert_eq!(variant.encode(bytes), expected, "{variant:?}");
+                assert_eq!(variant.encoded_len(bytes.len()), expected.len());
+                assert_eq!(variant.decode(&expected).unwrap(), *bytes, "{variant:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn backend_rejects_the_other_alphabet() {
+        assert!(Base64Variant::UrlSafe.decode("+/8=").is_err());
+        assert!(Base64Variant::Standard.decode("-_8=").is_err());
+        assert!(matches!(url_safe_to_spell("+/8="), Err(Error::Base64(_))));
+    }
+
+    #[test]
+    fn url_encoded_len_counts_escapes() {
+        assert_eq!(url_encoded_len("Zm9v"), 4);
+        assert_eq!(url_encoded_len("+/8="), 10);
+        assert_eq!(url_encoded_len("-_8"), 3);
+    }
+
+    #[test]
+    fn shortest_url_safe_decodes_with_the_variant_it_names() {
+        let spell = spell("shortest", vec![piece("psi:selector_caster", 0, 0)]);
+        let (encoded, variant) = shortest_url_safe(&spell).unwrap();
+        let compressed = variant.decode(&encoded).unwrap();
+        assert_eq!(
+            Spell::decode(&decompress(&compressed).unwrap()).unwrap(),
+            spell
+        );
+        for other in Base64Variant::ALL {
+            assert!(url_encoded_len(&encoded) <= url_encoded_len(&other.encode(&compressed)));
+        }
+    }
+
+    #[test]
+    fn estimated_url_safe_len_is_exact() {
+        let spell = spell(
+            "estimate",
+            vec![with_params(
+                piece("psi:trick_debug", 1, 0),
+                &[("_target", 3)],
+            )],
+        );
+        assert_eq!(
+            spell.estimated_url_safe_len().unwrap(),
+            spell.to_url_safe().unwrap().len()
+        );
+    }
+
+    #[test]
+    fn encoded_len_rounds_up_to_whole_characters() {
+        for len in 0..64 {
+            let bytes = vec![0xa5; len];
+            for variant in Base64Variant::ALL {
+                assert_eq!(variant.encoded_len(len), variant.encode(&bytes).len());
+            }
+        }
+    }
+
+    #[test]
+    fn recompressing_keeps_the_spell_bytes() {
+        let bin = spell("old", vec![piece("psi:selector_caster", 0, 0)])
+            .bin()
+            .unwrap();
+        let legacy_dict = b"psi:selector_caster psi:trick_debug".repeat(8);
+        let old = bytes_to_url_safe_with_dict(&bin, &legacy_dict).unwrap();
+
+        let upgraded = recompress_url_safe_with_dict(&old, &legacy_dict).unwrap();
+        assert_eq!(url_safe_to_bytes(&upgraded).unwrap(), bin);
+        assert_eq!(recompress_url_safe(&upgraded).unwrap(), upgraded);
+    }
+
+    #[test]
+    fn compression_stats_measure_each_stage() {
+        let spell = spell("stats", vec![piece("psi:selector_caster", 0, 0)]);
+        let stats = spell.compression_stats().unwrap();
+        assert_eq!(stats.bin_len, spell.bin().unwrap().len());
+        assert_eq!(stats.compressed_len, spell.compressed_len().unwrap());
+        assert_eq!(stats.base64_len, spell.to_url_safe().unwrap().len());
+    }
+
+    #[test]
+    fn compressed_base64_round_trips_in_every_variant() {
+        let bin = spell("variants", vec![piece("psi:selector_caster", 0, 0)])
+            .bin()
+            .unwrap();
+        for variant in Base64Variant::ALL {
+            let encoded = bytes_to_compressed_base64(&bin, variant).unwrap();
+            assert_eq!(compressed_base64_to_bytes(&encoded, variant).unwrap(), bin);
+        }
+        assert_eq!(
+            bytes_to_compressed_base64(&bin, Base64Variant::UrlSafe).unwrap(),
+            bytes_to_url_safe(&bin).unwrap()
+        );
+    }
+
+    // How every zstd frame starts, 0xFD2FB528 little-endian.
+    const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+    // The first byte of the compressed payload says which path was taken.
+    fn url_safe_payload(bin: &[u8]) -> Vec<u8> {
+        let url_safe = bytes_to_url_safe(bin).unwrap();
+        assert_eq!(url_safe_to_bytes(&url_safe).unwrap(), bin);
+        Base64Variant::UrlSafe.decode(&url_safe).unwrap()
+    }
+
+    #[test]
+    fn one_piece_spell_is_stored_raw() {
+        let spell = spell("a", vec![piece("psi:selector_caster", 0, 0)]);
+        let bin = spell.bin().unwrap();
+        let payload = url_safe_payload(&bin);
+        assert_eq!(payload[0], crate::compression::RAW_TAG);
+        assert_eq!(&payload[1..], bin);
+    }
+
+    #[test]
+    fn large_spell_is_compressed() {
+        let pieces = (0..9)
+            .flat_map(|y| {
+                (0..9).map(move |x| {
+                    let mut debug = with_params(piece("psi:trick_debug", x, y), &[("_target", 3)]);
+                    debug.data.comment = Some(format!("cell {x}, {y}"));
+                    debug
+                })
+            })
+            .collect();
+        let bin = spell("large", pieces).bin().unwrap();
+        let payload = url_safe_payload(&bin);
+        assert_eq!(payload[..4], ZSTD_MAGIC);
+        assert!(payload.len() < bin.len());
+    }
+
+    #[test]
+    fn checksum_catches_a_mistyped_character() {
+        let spell = spell("checked", vec![piece("psi:selector_caster", 0, 0)]);
+        let checked = spell_to_checked_url_safe(&spell).unwrap();
+        assert_eq!(url_safe_to_spell(&checked).unwrap(), spell);
+
+        // Past the tag and the checksum, so only the payload changes.
+        let at = checked.len() / 2;
+        let typo = if &checked[at..=at] == "A" { "B" } else { "A" };
+        let mangled = format!("{}{typo}{}", &checked[..at], &checked[at + 1..]);
+        assert!(matches!(
+            url_safe_to_spell(&mangled),
+            Err(Error::Decode(crate::DecodeError::ChecksumMismatch { .. }))
+        ));
+    }
+
+    #[test]
+    fn raw_base64_is_the_uncompressed_binary() {
+        let spell = spell("raw", vec![piece("psi:selector_caster", 0, 0)]);
+        let encoded = spell_to_raw_base64(&spell, Base64Variant::Standard).unwrap();
+        assert_eq!(
+            encoded,
+            Base64Variant::Standard.encode(&spell.bin().unwrap())
+        );
+        assert_eq!(
+            raw_base64_to_spell(&encoded, Base64Variant::Standard).unwrap(),
+            spell
+        );
+    }
+
+    #[test]
+    fn batches_convert_in_order() {
+        let spells = vec![
+            spell("first", vec![piece("psi:selector_caster", 0, 0)]),
+            spell("second", vec![]),
+        ];
+        let encoded = spells_to_url_safe(&spells).unwrap();
+        assert_eq!(encoded[1], spell_to_url_safe(&spells[1]).unwrap());
+        assert_eq!(url_safe_to_spells(&encoded).unwrap(), spells);
+    }
+
+    #[test]
+    fn batch_fails_on_any_bad_spell() {
+        let spells = [spell("fine", vec![]), spell("bad\0", vec![])];
+        assert!(matches!(
+            spells_to_url_safe(&spells),
+            Err(Error::Encode(crate::EncodeError::ReservedByte { .. }))
+        ));
+    }
+}
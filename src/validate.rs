@@ -0,0 +1,195 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use tsify::Tsify;
+
+use crate::{EncodeError, Side, Spell};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Tsify), tsify(into_wasm_abi, from_wasm_abi))]
+pub struct ValidationIssue {
+    /// `None` for problems with the spell as a whole, such as its name.
+    pub position: Option<(u8, u8)>,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Tsify), tsify(into_wasm_abi, from_wasm_abi))]
+pub struct ValidationIssues(pub Vec<ValidationIssue>);
+
+fn error_position(error: &EncodeError) -> Option<(u8, u8)> {
+    match error {
+        EncodeError::TooManyParams { x, y, .. }
+        | EncodeError::ConstantWithParams { x, y }
+        | EncodeError::AmbiguousKey { x, y } => Some((*x, *y)),
+        EncodeError::ReservedByte { position, .. } => *position,
+        EncodeError::NameTooLong { .. } => None,
+    }
+}
+
+impl Spell {
+    /// Everything that would stop the spell from encoding, followed by routing
+    /// problems that would stop it from casting.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues: Vec<_> = self
+            .encode_errors()
+            .into_iter()
+            .map(|error| ValidationIssue {
+                position: error_position(&error),
+                message: error.to_string(),
+            })
+            .collect();
+        issues.extend(self.validate_routing());
+        issues
+    }
+
+    pub fn validate_routing(&self) -> Vec<ValidationIssue> {
+        let grid: BTreeMap<_, _> = self.pieces.iter().map(|p| ((p.x, p.y), p)).collect();
+        let mut issues = Vec::new();
+
+        for piece in &self.pieces {
+            for (name, side) in piece.data.params.iter().flatten() {
+                let mut issue = |message| {
+                    issues.push(ValidationIssue {
+                        position: Some((piece.x, piece.y)),
+                        message,
+                    })
+                };
+                let side = match Side::try_from(*side) {
+                    Ok(side) => side,
+                    Err(byte) => {
+                        issue(format!(
+                            "param `{name}` has side {byte}, only 0 (off) to 4 (right) are legal"
+                        ));
+                        continue;
+                    }
+                };
+                let Some((dx, dy)) = side.offset() else {
+                    continue;
+                };
+
+                let target = (
+                    piece.x.checked_add_signed(dx),
+                    piece.y.checked_add_signed(dy),
+                );
+                let (Some(tx), Some(ty)) = target else {
+                    issue(format!("param `{name}` points outside the grid"));
+                    continue;
+                };
+
+                match grid.get(&(tx, ty)) {
+                    None => issue(format!("param `{name}` points at empty cell ({tx}, {ty})")),
+                    // Tricks evaluate to nothing, so no param can take their output.
+                    Some(source) if source.data.path().starts_with("trick_") => issue(format!(
+                        "param `{name}` points at trick `{}` at ({tx}, {ty}), which produces no value",
+                        source.data.key
+                    )),
+                    Some(_) => {}
+                }
+            }
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn positions(issues: &[ValidationIssue]) -> Vec<Option<(u8, u8)>> {
+        issues.iter().map(|issue| issue.position).collect()
+    }
+
+    #[test]
+    fn params_reading_a_neighbour_pass() {
+        let spell = spell(
+            "routed",
+            vec![
+                piece("psi:selector_caster", 0, 0),
+                with_params(piece("psi:trick_debug", 1, 0), &[("_target", 3)]),
+            ],
+        );
+        assert!(spell.validate_routing().is_empty());
+    }
+
+    #[test]
+    fn params_reading_nothing_are_reported() {
+        let spell = spell(
+            "misrouted",
+            vec![
+                // Top of row 0 is off the grid, the cell on its right is empty.
+                with_params(
+                    piece("psi:operator_sum", 0, 0),
+                    &[("_number1", 1), ("_number2", 4)],
+                ),
+                piece("psi:trick_debug", 0, 1),
+                with_params(piece("psi:operator_sum", 1, 1), &[("_number1", 3)]),
+            ],
+        );
+        let issues = spell.validate_routing();
+        assert_eq!(
+            issues.iter().map(|i| i.message.as_str()).collect::<Vec<_>>(),
+            vec![
+                "param `_number1` points outside the grid",
+                "param `_number2` points at empty cell (1, 0)",
+                "param `_number1` points at trick `psi:trick_debug` at (0, 1), which produces no value",
+            ]
+        );
+        assert_eq!(
+            positions(&issues),
+            vec![Some((0, 0)), Some((0, 0)), Some((1, 1))]
+        );
+    }
+
+    #[test]
+    fn validate_lists_encode_errors_before_routing() {
+        let mut both = with_params(piece("psi:constant_number", 0, 0), &[("_target", 4)]);
+        both.data.constant = Some("1".to_string());
+        let spell = spell(&"n".repeat(21), vec![both]);
+
+        let issues = spell.validate();
+        assert_eq!(positions(&issues), vec![None, Some((0, 0)), Some((0, 0))]);
+        assert_eq!(
+            issues[0].message,
+            EncodeError::NameTooLong { len: 21, max: 20 }.to_string()
+        );
+        assert_eq!(
+            issues[1].message,
+            EncodeError::ConstantWithParams { x: 0, y: 0 }.to_string()
+        );
+        assert_eq!(
+            issues[2].message,
+            "param `_target` points at empty cell (1, 0)"
+        );
+    }
+
+    #[test]
+    fn side_bytes_past_right_are_illegal() {
+        let spell = spell(
+            "sides",
+            vec![
+                piece("psi:selector_caster", 0, 0),
+                with_params(
+                    piece("psi:trick_debug", 1, 0),
+                    &[("_target", 3), ("_number", 0), ("_power", 5)],
+                ),
+            ],
+        );
+        let issues = spell.validate_routing();
+        assert_eq!(
+            issues,
+            vec![ValidationIssue {
+                position: Some((1, 0)),
+                message: "param `_power` has side 5, only 0 (off) to 4 (right) are legal"
+                    .to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Every function the crate exports to JS, next to the panic hook that setup
+//! installs. Each binding only converts arguments and turns `Error` into a `JsError`, which
+//! can only be built on wasm targets, so Rust callers should use the function
+//! each binding calls instead.
+
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "url-safe")]
+use crate::{Base64Variant, CompressionStats, Spells};
+use crate::{Spell, ValidationIssues};
+
+type JsResult<T> = Result<T, JsError>;
+
+#[wasm_bindgen(start)]
+pub fn main() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+}
+
+/// Borrowed slices still cost one copy from the JS Uint8Array into wasm memory,
+/// since wasm cannot read the JS heap, but unlike `Vec<u8>` the buffer is never
+/// handed over to Rust and is freed by the glue as soon as the call returns.
+/// Passing a `subarray` of a larger batch buffer is the cheapest way in.
+#[wasm_bindgen(js_name = "bytesToSpell")]
+pub fn bytes_to_spell(bytes: &[u8]) -> JsResult<Spell> {
+    let spell: Spell = Spell::decode(bytes)?;
+    Ok(spell)
+}
+
+#[wasm_bindgen(js_name = "spellToBytes")]
+pub fn spell_to_bytes(spell: Spell) -> Result<Vec<u8>, JsError> {
+    Ok(Vec::try_from(&spell)?)
+}
+
+#[cfg(feature = "snbt")]
+#[wasm_bindgen(js_name = "snbtToSpell")]
+pub fn snbt_to_spell(snbt: &str) -> JsResult<Spell> {
+    Ok(Spell::from_snbt(snbt)?)
+}
+
+#[cfg(feature = "snbt")]
+#[wasm_bindgen(js_name = "spellToSnbt")]
+pub fn spell_to_snbt(spell: Spell) -> JsResult<String> {
+    Ok(spell.to_snbt()?)
+}
+
+#[wasm_bindgen(js_name = "structuralFingerprint")]
+pub fn structural_fingerprint(spell: Spell) -> String {
+    format!("{:016x}", spell.structural_fingerprint())
+}
+
+#[wasm_bindgen(js_name = "validateSpell")]
+pub fn validate_spell(spell: Spell) -> ValidationIssues {
+    ValidationIssues(spell.validate())
+}
+
+#[wasm_bindgen(js_name = "spellToHex")]
+pub fn spell_to_hex(spell: Spell) -> JsResult<String> {
+    Ok(crate::spell_to_hex(&spell)?)
+}
+
+#[wasm_bindgen(js_name = "hexToSpell")]
+pub fn hex_to_spell(hex: &str) -> JsResult<Spell> {
+    Ok(crate::hex_to_spell(hex)?)
+}
+
+#[cfg(feature = "compression")]
+#[wasm_bindgen(js_name = "spellCompressedLen")]
+pub fn spell_compressed_len(spell: Spell) -> JsResult<usize> {
+    Ok(spell.compressed_len()?)
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "urlSafeToSpell")]
+pub fn url_safe_to_spell(url_safe: String) -> JsResult<Spell> {
+    Ok(crate::url_safe_to_spell(&url_safe)?)
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "spellToUrlSafe")]
+pub fn spell_to_url_safe(spell: Spell) -> JsResult<String> {
+    Ok(crate::spell_to_url_safe(&spell)?)
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "spellsToUrlSafe")]
+pub fn spells_to_url_safe(spells: Spells) -> JsResult<Vec<String>> {
+    Ok(crate::spells_to_url_safe(&spells.0)?)
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "urlSafeToSpells")]
+pub fn url_safe_to_spells(url_safe: Vec<String>) -> JsResult<Spells> {
+    Ok(Spells(crate::url_safe_to_spells(&url_safe)?))
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "spellToCheckedUrlSafe")]
+pub fn spell_to_checked_url_safe(spell: Spell) -> JsResult<String> {
+    Ok(crate::spell_to_checked_url_safe(&spell)?)
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "bytesToUrlSafe")]
+pub fn bytes_to_url_safe(bytes: &[u8]) -> JsResult<String> {
+    Ok(crate::bytes_to_url_safe(bytes)?)
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "urlSafeToBytes")]
+pub fn url_safe_to_bytes(url_safe: String) -> JsResult<Vec<u8>> {
+    Ok(crate::url_safe_to_bytes(&url_safe)?)
+}
+
+/// For destinations that reject the url-safe alphabet or padding. Strings from
+/// `spellToUrlSafe` are `Base64Variant.UrlSafe`.
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "bytesToBase64")]
+pub fn bytes_to_base64(bytes: &[u8], variant: Base64Variant) -> JsResult<String> {
+    Ok(crate::bytes_to_compressed_base64(bytes, variant)?)
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "base64ToBytes")]
+pub fn base64_to_bytes(encoded: &str, variant: Base64Variant) -> JsResult<Vec<u8>> {
+    Ok(crate::compressed_base64_to_bytes(encoded, variant)?)
+}
+
+#[cfg(feature = "url-safe")]
+#[wasm_bindgen(js_name = "compressionStats")]
+pub fn compression_stats(spell: Spell) -> JsResult<CompressionStats> {
+    Ok(spell.compression_stats()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn js_fingerprint_is_zero_padded_hex() {
+        assert_eq!(
+            structural_fingerprint(spell("empty", vec![])),
+            "cbf29ce484222325"
+        );
+        let spell = spell(
+            "two",
+            vec![
+                piece("psi:selector_caster", 0, 0),
+                with_params(piece("psi:trick_debug", 1, 0), &[("_target", 3)]),
+            ],
+        );
+        let hex = structural_fingerprint(spell.clone());
+        assert_eq!(hex.len(), 16);
+        assert_eq!(
+            u64::from_str_radix(&hex, 16).unwrap(),
+            spell.structural_fingerprint()
+        );
+
+        let mut translated = spell.clone();
+        for piece in &mut translated.pieces {
+            piece.x += 3;
+            piece.y += 2;
+        }
+        assert_eq!(structural_fingerprint(translated), hex);
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn js_snbt_matches_to_snbt() {
+        let spell = spell(
+            "snbt",
+            vec![with_params(
+                piece("psi:trick_debug", 1, 0),
+                &[("_target", 3)],
+            )],
+        );
+        assert_eq!(spell_to_snbt(spell.clone()).ok(), spell.to_snbt().ok());
+    }
+
+    #[test]
+    fn js_bytes_round_trip_through_a_borrowed_slice() {
+        let spell = spell(
+            "bytes",
+            vec![with_params(
+                piece("psi:trick_debug", 1, 0),
+                &[("_target", 3)],
+            )],
+        );
+        let bin = spell_to_bytes(spell.clone()).ok().unwrap();
+        // As a `subarray` of a bigger buffer would arrive.
+        let batch = [b"\xff\xff".as_slice(), &bin, b"\xff"].concat();
+        let slice = &batch[2..2 + bin.len()];
+        assert_eq!(bytes_to_spell(slice).ok(), Some(spell));
+    }
+
+    #[test]
+    fn js_validation_wraps_validate() {
+        let spell = spell(
+            "routing",
+            vec![with_params(
+                piece("psi:trick_debug", 0, 0),
+                &[("_target", 4)],
+            )],
+        );
+        let issues = validate_spell(spell.clone()).0;
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues, spell.validate());
+    }
+}
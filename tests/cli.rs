@@ -0,0 +1,50 @@
+// Runs the built `psi-encode` binary both ways: an SNBT file to a url-safe
+// string, then that string back to SNBT, which has to parse to the spell the
+// file held.
+
+use std::process::Command;
+
+use psi_spell_encode_wasm::Spell;
+
+const SNBT: &str = r#"{
+    modsRequired: [{modName: "psi", modVersion: "r1.1-78"}, {modName: "phi", modVersion: "1.2"}],
+    spellList: [
+        {data: {key: "psi:selector_caster", params: {}}, x: 3, y: 4},
+        {data: {comment: "how far", constantValue: "8", key: "psi:constant_number"}, x: 4, y: 3},
+        {data: {key: "psi:trick_blink", params: {_target: 3, _distance: 1}}, x: 4, y: 4},
+        {data: {key: "phi:trick_phase"}, x: 5, y: 4}
+    ],
+    spellName: "blink"
+}"#;
+
+fn psi_encode(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_psi-encode"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "psi-encode {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn snbt_file_survives_encode_then_decode() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("blink.snbt");
+    std::fs::write(&path, SNBT).unwrap();
+
+    let url_safe = psi_encode(&["--snbt", path.to_str().unwrap()]);
+    let url_safe = url_safe.trim_end();
+    assert_eq!(
+        url_safe.parse::<Spell>().unwrap(),
+        Spell::from_snbt(SNBT).unwrap()
+    );
+
+    let snbt = psi_encode(&["--decode", url_safe]);
+    assert_eq!(
+        Spell::from_snbt(&snbt).unwrap(),
+        Spell::from_snbt(SNBT).unwrap()
+    );
+}
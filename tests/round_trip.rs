@@ -0,0 +1,108 @@
+// Property test for the core invariant of the binary form: any spell `bin`
+// accepts decodes back to itself. The strategies only build spells that are
+// already in decoded shape (keys carry a namespace, comments are never empty),
+// and use no filters, so proptest can shrink a failure down to a small spell.
+
+use std::ops::RangeInclusive;
+
+use proptest::prelude::*;
+use psi_spell_encode_wasm::{Mod, Piece, Spell, SpellData, SpellParams};
+
+// A few of the builtin params, written as a table index.
+const BUILTINS: &[&str] = &["_target", "_number", "_number1", "_vector1", "_ray_start"];
+// Long custom names that come up on enough pieces to land in the param table.
+const INTERNED: &[&str] = &["_interned_alpha", "_interned_beta", "_interned_gamma"];
+
+#[derive(Debug, Clone)]
+enum Body {
+    Empty,
+    Constant(String),
+    Params(SpellParams),
+}
+
+fn param_name() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::sample::select(BUILTINS).prop_map(str::to_string),
+        prop::sample::select(INTERNED).prop_map(str::to_string),
+        "[a-z_][a-z0-9_]{0,15}",
+    ]
+}
+
+fn body() -> impl Strategy<Value = Body> {
+    prop_oneof![
+        Just(Body::Empty),
+        "[a-zA-Z0-9 .:é日-]{0,12}".prop_map(Body::Constant),
+        // Mostly a handful of params, sometimes up to `MAX_PARAMS`.
+        prop::collection::btree_map(param_name(), any::<u8>(), 0..=4).prop_map(Body::Params),
+        prop::collection::btree_map(param_name(), any::<u8>(), 0..=253).prop_map(Body::Params),
+    ]
+}
+
+fn key() -> impl Strategy<Value = String> {
+    (
+        prop_oneof![Just("psi".to_string()), "[a-z]{2,8}"],
+        "[a-z_]{1,16}",
+    )
+        .prop_map(|(namespace, path)| format!("{namespace}:{path}"))
+}
+
+fn piece(coord: RangeInclusive<u8>) -> impl Strategy<Value = Piece> {
+    (
+        coord.clone(),
+        coord,
+        key(),
+        body(),
+        prop::option::of("[a-zA-Z0-9 !?é日]{1,16}"),
+    )
+        .prop_map(|(x, y, key, body, comment)| {
+            let (params, constant) = match body {
+                Body::Empty => (None, None),
+                Body::Constant(constant) => (None, Some(constant)),
+                Body::Params(params) => (Some(params), None),
+            };
+            Piece {
+                data: SpellData {
+                    key,
+                    params,
+                    constant,
+                    comment,
+                    #[cfg(feature = "json")]
+                    extra: None,
+                },
+                x,
+                y,
+            }
+        })
+}
+
+fn spell() -> impl Strategy<Value = Spell> {
+    // Every coordinate fits the packed position byte.
+    let compact = prop::collection::vec(piece(0..=15), 0..12);
+    // The first piece reaches past it, switching to extended positions.
+    let extended = (
+        piece(16..=255),
+        prop::collection::vec(piece(0..=255), 0..12),
+    )
+        .prop_map(|(first, mut rest)| {
+            rest.insert(0, first);
+            rest
+        });
+    let mods = prop::collection::vec(("[a-z_]{1,10}", "[0-9a-z.-]{0,8}"), 0..4).prop_map(|mods| {
+        mods.into_iter()
+            .map(|(name, version)| Mod { name, version })
+            .collect()
+    });
+
+    ("[a-zA-Z0-9 ]{0,20}", mods, prop_oneof![compact, extended])
+        .prop_map(|(name, mods, pieces)| Spell { mods, pieces, name })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(4096))]
+
+    #[test]
+    fn decode_inverts_bin(spell in spell()) {
+        let bin = spell.bin().unwrap();
+        prop_assert_eq!(Spell::decode(&bin).unwrap(), spell);
+    }
+}